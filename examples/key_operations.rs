@@ -3,10 +3,12 @@ extern crate crypto;
 extern crate rustc_serialize;
 
 use std::env;
+use std::time::Duration;
 
+use vault::auth::DefaultAzureCredential;
 use vault::http::client::{Vault, AzureVault};
+use vault::jwk::{JsonWebKeyType, JsonWebKeyEncryptionAlgorithm, JsonWebKeySignatureAlgorithm};
 
-use rustc_serialize::base64::{FromBase64};
 use rustc_serialize::hex::FromHex;
 
 use crypto::digest::Digest;
@@ -14,16 +16,13 @@ use crypto::sha2::Sha512;
 
 fn main() {
     let mut vault = String::new();
-    let mut key = String::new();
-    let mut secret = String::new();
 
     get_arg(1, &mut vault);
-    get_arg(2, &mut key);
-    get_arg(3, &mut secret);
 
-    println!("vault: {:?}, key: {:?}, secret: {:?}", vault, key, secret);
+    println!("vault: {:?}", vault);
 
-    let mut client: AzureVault = Vault::new(&vault[..], &key[..], &secret[..]);
+    // AZURE_* environment variables, workload or managed identity, or `az login`
+    let mut client: AzureVault = Vault::new(&vault[..], Box::new(DefaultAzureCredential::new())).unwrap();
 
     display_current_keys_list(&mut client);
 
@@ -46,7 +45,7 @@ fn get_arg(index: usize, buf: &mut String){
     match env::args().nth(index) {
         Some(arg) => buf.push_str(&arg[..]),
         None => {
-            panic!("Usage: key_operations <vault_name> <key> <secret>");
+            panic!("Usage: key_operations <vault_name>");
         }
     };
 }
@@ -56,20 +55,18 @@ fn display_sign_verify(client: &mut AzureVault, key_name: &str, message: String)
     hasher.input_str(&message[..]);
     let hex = hasher.result_str().from_hex().unwrap();
     println!("SHA512 Hash for '{:?}': {:?}\n", message, hex);
-    let signature = client.sign(key_name, hex.clone()).unwrap();
+    let signature = client.sign(key_name, JsonWebKeySignatureAlgorithm::RS512, hex.clone()).unwrap();
     println!("Signature of hash: {:?}\n", signature);
-    let is_verified = client.verify(key_name, hex.clone(), signature.clone()).unwrap();
+    let is_verified = client.verify(key_name, JsonWebKeySignatureAlgorithm::RS512, hex.clone(), signature.clone()).unwrap();
     println!("is verified: {:?}\n", is_verified);
 }
 
 fn display_encrypt_decrypt(client: &mut AzureVault, key_name: &str, message: String){
     println!("Original Message: {:?}\n", message);
-    let encrypted_message = client.encrypt(key_name, message.as_bytes()).unwrap();
-    let encrypted_bits = &encrypted_message[..].from_base64().unwrap();
+    let encrypted_bits = client.encrypt(key_name, JsonWebKeyEncryptionAlgorithm::RSA_OAEP, message.as_bytes()).unwrap();
     println!("Encrypted Message: {:?}\n", encrypted_bits);
     println!("Length of Message: {:?}\n", encrypted_bits.len());
-    let decrypted_message = client.decrypt(key_name, &encrypted_bits[..]).unwrap();
-    let decrypted_bits = decrypted_message.from_base64().unwrap();
+    let decrypted_bits = client.decrypt(key_name, JsonWebKeyEncryptionAlgorithm::RSA_OAEP, &encrypted_bits[..]).unwrap();
     println!("Decrypted Message: {:?}\n", String::from_utf8(decrypted_bits).unwrap());
 }
 
@@ -85,24 +82,40 @@ fn display_key_by_name(client: &mut AzureVault, key_name: &str){
 fn delete_existing_key(client: &mut AzureVault, key_name: &str){
     let deleted_key_result = client.delete_key(key_name);
     match deleted_key_result {
-        Ok(Some(deleted_key)) =>    println!("Deleted Key with id: {:?}\n", deleted_key.key.kid),
+        Ok(Some(deleted_key)) =>    {
+            println!("Deleted Key with id: {:?}\n", deleted_key.key.kid);
+            // on soft-delete enabled vaults the name stays taken until the key is purged
+            if deleted_key.recovery_id.is_some() {
+                purge_deleted_key(client, key_name);
+            }
+        },
         Ok(None) =>                 println!("Didn't find Key {:?}\n", key_name),
         Err(err) =>                 println!("error: {:?}", err)
     }
 }
 
+fn purge_deleted_key(client: &mut AzureVault, key_name: &str){
+    let interval = Duration::from_secs(2);
+    client.wait_for_key_deletion(key_name, interval, 30).unwrap();
+    client.purge_deleted_key(key_name).unwrap();
+    let purged = client.wait_for_key_purge(key_name, interval, 30).unwrap();
+    println!("Purged Key {:?}: {:?}\n", key_name, purged);
+}
+
 fn insert_new_key(client: &mut AzureVault, key_name: &str){
     let key_ops_vec = vec!["verify", "decrypt", "encrypt", "sign"];
     let key_ops = key_ops_vec.iter().map(|&op| format!("{}", op)).collect();
-    let create_key = client.create_key(key_name, key_ops).unwrap();
+    let create_key = client.create_key(key_name, JsonWebKeyType::Rsa, None, key_ops).unwrap();
     println!("Created Key with id: {:?}\n", create_key.key.kid);
 }
 
 fn display_current_keys_list(client: &mut AzureVault){
-    let list = client.list();
-    match list {
-      Ok(Some(keys)) =>  println!("Current Key List: {:?}\n", keys),
-      Ok(None) =>       println!("No key list was returned..."),
-      Err(err) =>       println!("error: {:?}", err)
+    println!("Current Key List:");
+    for item in client.list(None) {
+      match item {
+        Ok(key) =>  println!("  {:?}", key.kid),
+        Err(err) => println!("error: {:?}", err)
+      }
     }
+    println!("");
 }
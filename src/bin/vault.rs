@@ -1,6 +1,128 @@
-#[macro_use] extern crate log;
-#[macro_use] extern crate vault;
+extern crate vault;
+
+use std::env;
+use std::process;
+
+use vault::VaultResult;
+use vault::auth::{AzureCliCredential, ChainedTokenCredential, DeviceCodeCredential, EnvironmentCredential};
+use vault::auth::{PersistentTokenCache, TokenCredential};
+use vault::http::client::{AzureVault, Vault};
+
+const USAGE: &'static str = "Usage: vault [--vault-name <name>] <command>
+
+Commands:
+  keys list             List the keys in the vault
+  keys get <name>       Show the public part of a key
+  secrets list          List the secrets in the vault
+  secrets get <name>    Print the value of a secret
+  auth logout           Forget the tokens kept from signing in
+
+The vault name can also be set with AZURE_KEYVAULT_NAME. The first command
+signs in with a device code unless AZURE_* environment variables are set or
+`az login` has been run; later commands reuse that sign-in, whose tokens are
+kept encrypted in the config directory.";
 
 fn main() {
-  //vault::connect("vault_name", "client_id", "client_secret");
+  let mut args: Vec<String> = env::args().skip(1).collect();
+  if args.len() == 2 && args[0] == "auth" && args[1] == "logout" {
+    if let Err(err) = logout() {
+      eprintln!("error: {}", err);
+      process::exit(1);
+    }
+    return;
+  }
+
+  let vault_name = match take_option(&mut args, "--vault-name").or_else(|| env::var("AZURE_KEYVAULT_NAME").ok()) {
+    Some(vault_name) => vault_name,
+    None => usage()
+  };
+
+  let mut client: AzureVault = match Vault::new(vault_name.as_ref(), credential()) {
+    Ok(client) => client,
+    Err(err) => {
+      eprintln!("error: {}", err);
+      process::exit(1);
+    }
+  };
+  let arg = |index: usize| args.get(index).map(|arg| arg.as_ref());
+  let result = match (arg(0), arg(1), arg(2)) {
+    (Some("keys"), Some("list"), None) => list_keys(&mut client),
+    (Some("keys"), Some("get"), Some(name)) => get_key(&mut client, name),
+    (Some("secrets"), Some("list"), None) => list_secrets(&mut client),
+    (Some("secrets"), Some("get"), Some(name)) => get_secret(&mut client, name),
+    _ => usage()
+  };
+  if let Err(err) = result {
+    eprintln!("error: {}", err);
+    process::exit(1);
+  }
+}
+
+fn usage() -> ! {
+  eprintln!("{}", USAGE);
+  process::exit(2);
+}
+
+// removes `--name value` from the arguments and returns the value
+fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+  match args.iter().position(|arg| arg == name) {
+    Some(index) if index + 1 < args.len() => {
+      let value = args.remove(index + 1);
+      args.remove(index);
+      Some(value)
+    },
+    _ => None
+  }
+}
+
+// service principals and `az login` first, then a device code sign-in whose
+// tokens are kept in the config directory
+fn credential() -> Box<TokenCredential> {
+  let mut device_code = DeviceCodeCredential::new();
+  if let Some(cache) = PersistentTokenCache::in_config_dir() {
+    device_code = device_code.with_persistent_cache(cache);
+  }
+  Box::new(ChainedTokenCredential::new(vec![
+    ("EnvironmentCredential", Box::new(EnvironmentCredential::new()) as Box<TokenCredential>),
+    ("AzureCliCredential", Box::new(AzureCliCredential::new()) as Box<TokenCredential>),
+    ("DeviceCodeCredential", Box::new(device_code) as Box<TokenCredential>),
+  ]))
+}
+
+fn logout() -> VaultResult<()> {
+  if let Some(cache) = PersistentTokenCache::in_config_dir() {
+    try!(cache.clear());
+  }
+  println!("signed out");
+  Ok(())
+}
+
+fn list_keys(client: &mut AzureVault) -> VaultResult<()> {
+  for key in client.list(None) {
+    println!("{}", try!(key).kid);
+  }
+  Ok(())
+}
+
+fn get_key(client: &mut AzureVault, name: &str) -> VaultResult<()> {
+  match try!(client.get_key(name)) {
+    Some(key) => println!("{} {} {}", key.key.kid, key.key.kty, key.key.key_ops.join(",")),
+    None => println!("key {} not found", name)
+  }
+  Ok(())
+}
+
+fn list_secrets(client: &mut AzureVault) -> VaultResult<()> {
+  for secret in client.list_secrets(None) {
+    println!("{}", try!(secret).id);
+  }
+  Ok(())
+}
+
+fn get_secret(client: &mut AzureVault, name: &str) -> VaultResult<()> {
+  match try!(client.get_secret(name, None)) {
+    Some(secret) => println!("{}", secret.value),
+    None => println!("secret {} not found", name)
+  }
+  Ok(())
 }
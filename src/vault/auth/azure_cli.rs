@@ -0,0 +1,141 @@
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+
+use rustc_serialize::json::Json;
+
+use auth::{AccessToken, TokenCredential};
+use auth::{redeem_refresh_token, resource_for};
+use error::{Error, VaultResult};
+
+/// Client id of the Azure CLI, which its refresh tokens are bound to.
+pub const AZURE_CLI_CLIENT_ID: &'static str = "04b07795-8ddb-461a-bbee-02f9e1bf7b46";
+
+// tokens this close to expiry are refreshed rather than used
+const EXPIRY_SKEW: i64 = 300;
+
+/// Uses the account signed in with `az login`, by reading the token cache the
+/// Azure CLI keeps in `~/.azure` (or `AZURE_CONFIG_DIR`). Cached access tokens
+/// are used while they are valid; otherwise the cached refresh token is redeemed.
+#[derive(Debug, Clone)]
+pub struct AzureCliCredential {
+  cache_path: PathBuf,
+  authority_host: Option<String>,
+}
+
+impl AzureCliCredential {
+  pub fn new() -> AzureCliCredential {
+    let config_dir = env::var("AZURE_CONFIG_DIR").ok().map(PathBuf::from)
+      .or_else(|| env::home_dir().map(|home| home.join(".azure")))
+      .unwrap_or_else(|| PathBuf::from(".azure"));
+    AzureCliCredential::from_cache_file(config_dir.join("msal_token_cache.json"))
+  }
+
+  /// Reads an MSAL token cache at another path.
+  pub fn from_cache_file(cache_path: PathBuf) -> AzureCliCredential {
+    AzureCliCredential { cache_path: cache_path, authority_host: None }
+  }
+
+  /// Redeems refresh tokens at another authority than the environment they were
+  /// issued in, e.g. `https://login.chinacloudapi.cn`.
+  pub fn with_authority_host(mut self, authority_host: &str) -> AzureCliCredential {
+    self.authority_host = Some(authority_host.to_string());
+    self
+  }
+
+  fn read_cache(&self) -> VaultResult<Json> {
+    let mut contents = String::new();
+    let mut file = try!(File::open(&self.cache_path).map_err(|err| {
+      Error::CredentialUnavailable(format!("no Azure CLI token cache at {} ({}); run `az login`", self.cache_path.display(), err))
+    }));
+    try!(file.read_to_string(&mut contents));
+    Json::from_str(contents.as_ref())
+      .map_err(|err| Error::Authentication(format!("Azure CLI token cache is not JSON: {}", err), None))
+  }
+
+  // a cached access token, else one redeemed with a cached refresh token
+  fn token(&self, scopes: &[&str]) -> VaultResult<AccessToken> {
+    let resource = try!(resource_for(scopes));
+    let cache = try!(self.read_cache());
+    match cached_access_token(&cache, resource.as_ref()) {
+      Some(token) => Ok(token),
+      None => self.redeem(&cache, resource.as_ref(), None)
+    }
+  }
+
+  // a token redeemed with a cached refresh token, never a cached access token;
+  // used when the vault rejected a token or asked for claims
+  fn new_token(&self, scopes: &[&str], challenge: Option<&str>) -> VaultResult<AccessToken> {
+    let resource = try!(resource_for(scopes));
+    let cache = try!(self.read_cache());
+    self.redeem(&cache, resource.as_ref(), challenge)
+  }
+
+  fn redeem(&self, cache: &Json, resource: &str, challenge: Option<&str>) -> VaultResult<AccessToken> {
+    for entry in entries(cache, "RefreshToken") {
+      let (secret, environment) = match (field(entry, "secret"), field(entry, "environment")) {
+        (Some(secret), Some(environment)) => (secret, environment),
+        _ => continue
+      };
+      let client_id = field(entry, "client_id").unwrap_or(AZURE_CLI_CLIENT_ID);
+      let tenant_id = env::var("AZURE_TENANT_ID").ok()
+        .or_else(|| tenant_of(cache, entry))
+        .unwrap_or("organizations".to_string());
+      let authority_host = self.authority_host.clone().unwrap_or_else(|| format!("https://{}", environment));
+      let (token, _) = try!(redeem_refresh_token(authority_host.as_ref(), tenant_id.as_ref(), client_id, secret, resource, challenge));
+      return Ok(token);
+    }
+    Err(Error::CredentialUnavailable(format!("the Azure CLI token cache holds no usable token for {}; run `az login`", resource)))
+  }
+}
+
+impl TokenCredential for AzureCliCredential {
+  fn get_token(&self, scopes: &[&str]) -> VaultResult<AccessToken> {
+    self.token(scopes)
+  }
+
+  // the cached access token may be the one the vault rejected
+  fn refresh_token(&self, scopes: &[&str]) -> VaultResult<AccessToken> {
+    self.new_token(scopes, None)
+  }
+
+  fn get_token_with_claims(&self, scopes: &[&str], claims: &str) -> VaultResult<AccessToken> {
+    self.new_token(scopes, Some(claims))
+  }
+}
+
+// access tokens are keyed by scopes such as `https://vault.azure.net/user_impersonation`
+fn cached_access_token(cache: &Json, resource: &str) -> Option<AccessToken> {
+  let resource_scope = format!("{}/", resource);
+  entries(cache, "AccessToken").into_iter()
+    .filter(|entry| field(entry, "target").map(|target| target.split(' ').any(|scope| scope.starts_with(&resource_scope[..]))).unwrap_or(false))
+    .filter_map(|entry| {
+      let expires_on = field(entry, "expires_on").and_then(|value| value.parse::<i64>().ok());
+      match (field(entry, "secret"), expires_on) {
+        (Some(secret), Some(expires_on)) => Some(AccessToken::new(secret.to_string(), expires_on)),
+        _ => None
+      }
+    })
+    .find(|token| !token.expires_within(EXPIRY_SKEW))
+}
+
+fn entries<'c>(cache: &'c Json, section: &str) -> Vec<&'c Json> {
+  cache.find(section)
+    .and_then(|section| section.as_object())
+    .map(|section| section.values().collect())
+    .unwrap_or(Vec::new())
+}
+
+fn field<'c>(entry: &'c Json, name: &str) -> Option<&'c str> {
+  entry.find(name).and_then(|value| value.as_string())
+}
+
+// the tenant the account signed in to, from its account entry
+fn tenant_of(cache: &Json, refresh_token: &Json) -> Option<String> {
+  let home_account_id = field(refresh_token, "home_account_id");
+  entries(cache, "Account").into_iter()
+    .find(|account| field(account, "home_account_id") == home_account_id)
+    .and_then(|account| field(account, "realm"))
+    .map(|realm| realm.to_string())
+}
@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, ThreadId};
+
+use auth::{AccessToken, TokenCredential};
+use error::VaultResult;
+
+/// Seconds before `expires_on` at which a cached token is refreshed by default.
+pub const DEFAULT_REFRESH_SKEW: i64 = 300;
+
+/// What a cached token was issued for.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+  pub tenant_id: String,
+  pub scope: String,
+  pub client_id: String,
+}
+
+/// Access tokens shared between clients and threads, refreshed shortly before
+/// they expire. Only one refresh runs at a time for each key; other callers
+/// wait for it and use its result.
+pub struct TokenCache {
+  skew: i64,
+  entries: Mutex<HashMap<CacheKey, Arc<Entry>>>,
+}
+
+// The token for one key. Its lock is not held while the token is refreshed, so
+// a credential that itself uses the cache can look the key up again; threads
+// other than the one refreshing wait on `refreshed` instead.
+struct Entry {
+  state: Mutex<EntryState>,
+  refreshed: Condvar,
+}
+
+struct EntryState {
+  token: Option<AccessToken>,
+  refreshing: Option<ThreadId>,
+}
+
+// Ends the refresh an entry is marked with, even when the credential panics.
+struct Refreshing<'a> {
+  entry: &'a Entry,
+}
+
+impl<'a> Drop for Refreshing<'a> {
+  fn drop(&mut self) {
+    let mut state = self.entry.state.lock().unwrap_or_else(|err| err.into_inner());
+    state.refreshing = None;
+    self.entry.refreshed.notify_all();
+  }
+}
+
+impl TokenCache {
+  pub fn new() -> TokenCache {
+    TokenCache::with_skew(DEFAULT_REFRESH_SKEW)
+  }
+
+  /// Refreshes tokens `skew` seconds before they expire.
+  pub fn with_skew(skew: i64) -> TokenCache {
+    TokenCache { skew: skew, entries: Mutex::new(HashMap::new()) }
+  }
+
+  /// Seconds before `expires_on` at which `get_or_refresh` renews a token.
+  pub fn skew(&self) -> i64 {
+    self.skew
+  }
+
+  /// The cached token for `key`, or the one `refresh` returns when there is
+  /// none or it is about to expire.
+  pub fn get_or_refresh<F>(&self, key: CacheKey, refresh: F) -> VaultResult<AccessToken> where F: FnOnce() -> VaultResult<AccessToken> {
+    let skew = self.skew;
+    self.get_or_refresh_within(key, skew, refresh)
+  }
+
+  /// Like `get_or_refresh`, renewing the token `skew` seconds before it expires
+  /// rather than at the skew of the cache.
+  pub fn get_or_refresh_within<F>(&self, key: CacheKey, skew: i64, refresh: F) -> VaultResult<AccessToken> where F: FnOnce() -> VaultResult<AccessToken> {
+    self.fetch(key, |token| !token.expires_within(skew), refresh)
+  }
+
+  /// Replaces the token for `key` with the one `refresh` returns, whether or not
+  /// the cached one has expired. Given the token a vault `rejected`, a cached
+  /// token other than it that is not within `skew` seconds of expiring is the
+  /// result of a refresh that raced this one, and is returned without
+  /// refreshing again.
+  pub fn refresh<F>(&self, key: CacheKey, rejected: Option<&AccessToken>, skew: i64, refresh: F) -> VaultResult<AccessToken> where F: FnOnce() -> VaultResult<AccessToken> {
+    self.fetch(key, |token| match rejected {
+      Some(rejected) => token.token != rejected.token && !token.expires_within(skew),
+      None => false
+    }, refresh)
+  }
+
+  pub fn remove(&self, key: &CacheKey) {
+    self.entries.lock().unwrap().remove(key);
+  }
+
+  pub fn clear(&self) {
+    self.entries.lock().unwrap().clear();
+  }
+
+  // Waits for a refresh of `key` on another thread to finish, then returns the
+  // cached token if `usable` accepts it and otherwise the one `refresh` returns.
+  // A refresh already under way on this thread (a credential that uses this
+  // cache itself) is not waited for.
+  fn fetch<P, F>(&self, key: CacheKey, usable: P, refresh: F) -> VaultResult<AccessToken>
+    where P: Fn(&AccessToken) -> bool, F: FnOnce() -> VaultResult<AccessToken> {
+    let entry = self.entry(key);
+    let current = thread::current().id();
+    let _refreshing = {
+      let mut state = entry.state.lock().unwrap();
+      while state.refreshing.map_or(false, |thread| thread != current) {
+        state = entry.refreshed.wait(state).unwrap();
+      }
+      if let Some(ref token) = state.token {
+        if usable(token) {
+          return Ok(token.clone());
+        }
+      }
+      if state.refreshing.is_none() {
+        state.refreshing = Some(current);
+        Some(Refreshing { entry: &entry })
+      } else {
+        None
+      }
+    };
+    let token = try!(refresh());
+    entry.state.lock().unwrap().token = Some(token.clone());
+    Ok(token)
+  }
+
+  fn entry(&self, key: CacheKey) -> Arc<Entry> {
+    self.entries.lock().unwrap().entry(key).or_insert_with(|| Arc::new(Entry {
+      state: Mutex::new(EntryState { token: None, refreshing: None }),
+      refreshed: Condvar::new()
+    })).clone()
+  }
+}
+
+/// A credential whose tokens are kept in a shared `TokenCache`.
+pub struct CachedTokenCredential {
+  cache: Arc<TokenCache>,
+  tenant_id: String,
+  client_id: String,
+  inner: Box<TokenCredential>,
+}
+
+impl CachedTokenCredential {
+  /// Caches the tokens of `inner` under its tenant and client id, which keep
+  /// them apart from those of other credentials sharing the cache.
+  pub fn new(cache: Arc<TokenCache>, tenant_id: &str, client_id: &str, inner: Box<TokenCredential>) -> CachedTokenCredential {
+    CachedTokenCredential {
+      cache: cache,
+      tenant_id: tenant_id.to_string(),
+      client_id: client_id.to_string(),
+      inner: inner
+    }
+  }
+
+  fn key(&self, scopes: &[&str]) -> CacheKey {
+    CacheKey {
+      tenant_id: self.tenant_id.clone(),
+      scope: scopes.join(" "),
+      client_id: self.client_id.clone()
+    }
+  }
+}
+
+impl TokenCredential for CachedTokenCredential {
+  fn get_token(&self, scopes: &[&str]) -> VaultResult<AccessToken> {
+    self.cache.get_or_refresh(self.key(scopes), || self.inner.get_token(scopes))
+  }
+
+  fn refresh_token(&self, scopes: &[&str]) -> VaultResult<AccessToken> {
+    self.cache.refresh(self.key(scopes), None, self.cache.skew(), || self.inner.refresh_token(scopes))
+  }
+
+  fn get_token_with_claims(&self, scopes: &[&str], claims: &str) -> VaultResult<AccessToken> {
+    self.cache.refresh(self.key(scopes), None, self.cache.skew(), || self.inner.get_token_with_claims(scopes, claims))
+  }
+}
@@ -0,0 +1,166 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::str::from_utf8;
+
+use openssl::error::ErrorStack;
+use openssl::hash::MessageDigest;
+use openssl::pkcs12::Pkcs12;
+use openssl::pkey::{PKey, Private};
+use openssl::rand::rand_bytes;
+use openssl::sign::Signer;
+use openssl::x509::X509;
+
+use rustc_serialize::base64::{ToBase64, URL_SAFE};
+use rustc_serialize::json;
+
+use auth::{AccessToken, TokenCredential, AZURE_PUBLIC_CLOUD, JWT_BEARER};
+use auth::{claims_param, now, request_token, resource_for, token_url};
+use error::{Error, VaultResult};
+
+// lifetime of a client assertion, in seconds
+const ASSERTION_LIFETIME: i64 = 600;
+
+#[derive(RustcEncodable)]
+struct JwtHeader<'a> {
+  alg: &'a str,
+  typ: &'a str,
+  x5t: &'a str,
+}
+
+#[derive(RustcEncodable)]
+struct JwtClaims<'a> {
+  aud: &'a str,
+  iss: &'a str,
+  sub: &'a str,
+  jti: &'a str,
+  nbf: i64,
+  exp: i64,
+}
+
+/// Authenticates a service principal with a certificate, by signing a
+/// `client_assertion` JWT with its private key.
+pub struct CertificateCredential {
+  authority_host: String,
+  tenant_id: String,
+  client_id: String,
+  key: PKey<Private>,
+  // base64url SHA-1 thumbprint of the DER certificate
+  thumbprint: String,
+}
+
+impl CertificateCredential {
+  /// A PEM bundle holding the certificate and an unencrypted private key.
+  pub fn from_pem(tenant_id: &str, client_id: &str, pem: &str) -> VaultResult<CertificateCredential> {
+    let cert = try!(X509::from_pem(pem.as_bytes()).map_err(|err| invalid("certificate", err)));
+    let key = try!(PKey::private_key_from_pem(pem.as_bytes()).map_err(|err| invalid("private key", err)));
+    CertificateCredential::new(tenant_id, client_id, cert, key)
+  }
+
+  /// A PKCS#12 archive holding the certificate and its private key.
+  pub fn from_pfx(tenant_id: &str, client_id: &str, pfx: &[u8], password: Option<&str>) -> VaultResult<CertificateCredential> {
+    let archive = try!(Pkcs12::from_der(pfx).map_err(|err| invalid("PFX", err)));
+    let parsed = try!(archive.parse2(password.unwrap_or("")).map_err(|err| invalid("PFX", err)));
+    match (parsed.cert, parsed.pkey) {
+      (Some(cert), Some(key)) => CertificateCredential::new(tenant_id, client_id, cert, key),
+      _ => Err(Error::InvalidArgument(format!("PFX must hold both a certificate and its private key")))
+    }
+  }
+
+  /// Reads a PEM bundle or, for anything else, a PFX archive.
+  pub fn from_file<P: AsRef<Path>>(tenant_id: &str, client_id: &str, path: P, password: Option<&str>) -> VaultResult<CertificateCredential> {
+    let mut contents = Vec::new();
+    let mut file = try!(File::open(path));
+    try!(file.read_to_end(&mut contents));
+    match from_utf8(&contents[..]) {
+      Ok(pem) if pem.contains("-----BEGIN ") => CertificateCredential::from_pem(tenant_id, client_id, pem),
+      _ => CertificateCredential::from_pfx(tenant_id, client_id, &contents[..], password)
+    }
+  }
+
+  fn new(tenant_id: &str, client_id: &str, cert: X509, key: PKey<Private>) -> VaultResult<CertificateCredential> {
+    let thumbprint = try!(cert.digest(MessageDigest::sha1()).map_err(|err| invalid("certificate", err)));
+    Ok(CertificateCredential {
+      authority_host: AZURE_PUBLIC_CLOUD.to_string(),
+      tenant_id: tenant_id.to_string(),
+      client_id: client_id.to_string(),
+      key: key,
+      thumbprint: thumbprint.to_base64(URL_SAFE)
+    })
+  }
+
+  /// Uses another authority, e.g. `https://login.chinacloudapi.cn`.
+  pub fn with_authority_host(mut self, authority_host: &str) -> CertificateCredential {
+    self.authority_host = authority_host.to_string();
+    self
+  }
+
+  /// The base64url SHA-1 thumbprint sent as the assertion's `x5t` header.
+  pub fn thumbprint(&self) -> &str {
+    self.thumbprint.as_ref()
+  }
+
+  /// Builds and signs an RS256 assertion for the given token endpoint.
+  pub fn client_assertion(&self, audience: &str) -> VaultResult<String> {
+    let header = JwtHeader { alg: "RS256", typ: "JWT", x5t: self.thumbprint.as_ref() };
+    let jti = try!(new_jti());
+    let issued = now();
+    let claims = JwtClaims {
+      aud: audience,
+      iss: self.client_id.as_ref(),
+      sub: self.client_id.as_ref(),
+      jti: jti.as_ref(),
+      nbf: issued,
+      exp: issued + ASSERTION_LIFETIME
+    };
+    let signing_input = format!("{}.{}",
+                                json::encode(&header).unwrap().as_bytes().to_base64(URL_SAFE),
+                                json::encode(&claims).unwrap().as_bytes().to_base64(URL_SAFE));
+    let signature = try!(self.sign(signing_input.as_bytes()).map_err(|err| {
+      Error::Authentication(format!("could not sign client assertion: {}", err), None)
+    }));
+    Ok(format!("{}.{}", signing_input, signature.to_base64(URL_SAFE)))
+  }
+
+  fn sign(&self, data: &[u8]) -> Result<Vec<u8>, ErrorStack> {
+    let mut signer = try!(Signer::new(MessageDigest::sha256(), &self.key));
+    try!(signer.update(data));
+    signer.sign_to_vec()
+  }
+
+  fn token(&self, scopes: &[&str], challenge: Option<&str>) -> VaultResult<AccessToken> {
+    let resource = try!(resource_for(scopes));
+    let claims = try!(claims_param(challenge));
+    let url = token_url(&self.authority_host, &self.tenant_id);
+    let assertion = try!(self.client_assertion(url.as_ref()));
+    request_token(url.as_ref(), &[("grant_type", "client_credentials"),
+                                  ("client_id", self.client_id.as_ref()),
+                                  ("client_assertion_type", JWT_BEARER),
+                                  ("client_assertion", assertion.as_ref()),
+                                  ("resource", resource.as_ref()),
+                                  ("claims", claims.as_ref())])
+  }
+}
+
+impl TokenCredential for CertificateCredential {
+  fn get_token(&self, scopes: &[&str]) -> VaultResult<AccessToken> {
+    self.token(scopes, None)
+  }
+
+  fn get_token_with_claims(&self, scopes: &[&str], claims: &str) -> VaultResult<AccessToken> {
+    self.token(scopes, Some(claims))
+  }
+}
+
+fn invalid(what: &str, err: ErrorStack) -> Error {
+  Error::InvalidArgument(format!("could not load {}: {}", what, err))
+}
+
+// a random GUID, so the token endpoint can reject replayed assertions
+fn new_jti() -> VaultResult<String> {
+  let mut bytes = [0u8; 16];
+  try!(rand_bytes(&mut bytes).map_err(|err| Error::Authentication(format!("could not generate jti: {}", err), None)));
+  let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+  let hex = hex.concat();
+  Ok(format!("{}-{}-{}-{}-{}", &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..32]))
+}
@@ -0,0 +1,50 @@
+use auth::{AccessToken, TokenCredential, AZURE_PUBLIC_CLOUD};
+use auth::{claims_param, request_token, resource_for, token_url};
+use error::VaultResult;
+
+/// Authenticates a service principal with its client id and a shared secret.
+#[derive(Debug, Clone)]
+pub struct ClientSecretCredential {
+  authority_host: String,
+  tenant_id: String,
+  client_id: String,
+  client_secret: String,
+}
+
+impl ClientSecretCredential {
+  pub fn new(tenant_id: &str, client_id: &str, client_secret: &str) -> ClientSecretCredential {
+    ClientSecretCredential {
+      authority_host: AZURE_PUBLIC_CLOUD.to_string(),
+      tenant_id: tenant_id.to_string(),
+      client_id: client_id.to_string(),
+      client_secret: client_secret.to_string()
+    }
+  }
+
+  /// Uses another authority, e.g. `https://login.chinacloudapi.cn`.
+  pub fn with_authority_host(mut self, authority_host: &str) -> ClientSecretCredential {
+    self.authority_host = authority_host.to_string();
+    self
+  }
+
+  fn token(&self, scopes: &[&str], challenge: Option<&str>) -> VaultResult<AccessToken> {
+    let resource = try!(resource_for(scopes));
+    let claims = try!(claims_param(challenge));
+    let url = token_url(&self.authority_host, &self.tenant_id);
+    request_token(url.as_ref(), &[("grant_type", "client_credentials"),
+                                  ("client_id", self.client_id.as_ref()),
+                                  ("client_secret", self.client_secret.as_ref()),
+                                  ("resource", resource.as_ref()),
+                                  ("claims", claims.as_ref())])
+  }
+}
+
+impl TokenCredential for ClientSecretCredential {
+  fn get_token(&self, scopes: &[&str]) -> VaultResult<AccessToken> {
+    self.token(scopes, None)
+  }
+
+  fn get_token_with_claims(&self, scopes: &[&str], claims: &str) -> VaultResult<AccessToken> {
+    self.token(scopes, Some(claims))
+  }
+}
@@ -0,0 +1,129 @@
+use std::env;
+use std::sync::Mutex;
+
+use auth::{AccessToken, TokenCredential};
+use auth::{AzureCliCredential, EnvironmentCredential, ManagedIdentityCredential, UserAssignedId, WorkloadIdentityCredential};
+use error::{Error, VaultResult};
+
+/// Tries a list of named credentials in order and uses the first that returns a
+/// token. Once one has succeeded it is tried first from then on. Only sources
+/// that are unavailable (`Error::CredentialUnavailable`) are passed over; any
+/// other error is returned as it is. When every source is unavailable, the
+/// error names each one and the reason.
+pub struct ChainedTokenCredential {
+  sources: Vec<(&'static str, Box<TokenCredential>)>,
+  // index of the source that last returned a token
+  last_used: Mutex<Option<usize>>,
+}
+
+impl ChainedTokenCredential {
+  pub fn new(sources: Vec<(&'static str, Box<TokenCredential>)>) -> ChainedTokenCredential {
+    ChainedTokenCredential { sources: sources, last_used: Mutex::new(None) }
+  }
+
+  // the first token `request` gets from a source, starting with the last one that worked
+  fn first_token<F>(&self, request: F) -> VaultResult<AccessToken> where F: Fn(&TokenCredential) -> VaultResult<AccessToken> {
+    let last_used = *self.last_used.lock().unwrap();
+    let mut failures = Vec::new();
+    if let Some(index) = last_used {
+      let name = self.sources[index].0;
+      match request(&*self.sources[index].1) {
+        Ok(token) => return Ok(token),
+        Err(err @ Error::CredentialUnavailable(_)) => {
+          debug!("{} is no longer available: {}", name, err);
+          failures.push(format!("{}: {}", name, err));
+        },
+        Err(err) => return Err(err)
+      }
+    }
+
+    for (index, &(name, ref source)) in self.sources.iter().enumerate() {
+      if Some(index) == last_used {
+        continue;
+      }
+      match request(&**source) {
+        Ok(token) => {
+          debug!("authenticated with {}", name);
+          *self.last_used.lock().unwrap() = Some(index);
+          return Ok(token);
+        },
+        Err(err @ Error::CredentialUnavailable(_)) => failures.push(format!("{}: {}", name, err)),
+        Err(err) => {
+          debug!("{} failed: {}", name, err);
+          return Err(err);
+        }
+      }
+    }
+    Err(Error::Authentication(format!("no credential returned a token:\n  {}", failures.join("\n  ")), None))
+  }
+}
+
+impl TokenCredential for ChainedTokenCredential {
+  fn get_token(&self, scopes: &[&str]) -> VaultResult<AccessToken> {
+    self.first_token(|source| source.get_token(scopes))
+  }
+
+  fn refresh_token(&self, scopes: &[&str]) -> VaultResult<AccessToken> {
+    self.first_token(|source| source.refresh_token(scopes))
+  }
+
+  fn get_token_with_claims(&self, scopes: &[&str], claims: &str) -> VaultResult<AccessToken> {
+    self.first_token(|source| source.get_token_with_claims(scopes, claims))
+  }
+}
+
+/// The credential chain for applications that should run unchanged on a
+/// developer machine and in Azure. It tries, in order:
+///
+/// 1. `EnvironmentCredential`
+/// 2. `WorkloadIdentityCredential`, when `AZURE_FEDERATED_TOKEN_FILE` is set
+/// 3. `ManagedIdentityCredential`, user-assigned when `AZURE_CLIENT_ID` is set
+/// 4. `AzureCliCredential`
+pub struct DefaultAzureCredential {
+  chain: ChainedTokenCredential,
+}
+
+impl DefaultAzureCredential {
+  pub fn new() -> DefaultAzureCredential {
+    let workload_identity: Box<TokenCredential> = match WorkloadIdentityCredential::from_env() {
+      Ok(credential) => Box::new(credential),
+      Err(Error::CredentialUnavailable(reason)) => Box::new(Unavailable(reason)),
+      Err(err) => Box::new(Unavailable(err.to_string()))
+    };
+    let managed_identity = match env::var("AZURE_CLIENT_ID") {
+      Ok(client_id) => ManagedIdentityCredential::user_assigned(UserAssignedId::ClientId(client_id)),
+      Err(_) => ManagedIdentityCredential::new()
+    };
+    DefaultAzureCredential {
+      chain: ChainedTokenCredential::new(vec![
+        ("EnvironmentCredential", Box::new(EnvironmentCredential::new()) as Box<TokenCredential>),
+        ("WorkloadIdentityCredential", workload_identity),
+        ("ManagedIdentityCredential", Box::new(managed_identity) as Box<TokenCredential>),
+        ("AzureCliCredential", Box::new(AzureCliCredential::new()) as Box<TokenCredential>),
+      ])
+    }
+  }
+}
+
+impl TokenCredential for DefaultAzureCredential {
+  fn get_token(&self, scopes: &[&str]) -> VaultResult<AccessToken> {
+    self.chain.get_token(scopes)
+  }
+
+  fn refresh_token(&self, scopes: &[&str]) -> VaultResult<AccessToken> {
+    self.chain.refresh_token(scopes)
+  }
+
+  fn get_token_with_claims(&self, scopes: &[&str], claims: &str) -> VaultResult<AccessToken> {
+    self.chain.get_token_with_claims(scopes, claims)
+  }
+}
+
+// a source the environment does not configure
+struct Unavailable(String);
+
+impl TokenCredential for Unavailable {
+  fn get_token(&self, _: &[&str]) -> VaultResult<AccessToken> {
+    Err(Error::CredentialUnavailable(self.0.clone()))
+  }
+}
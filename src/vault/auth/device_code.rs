@@ -0,0 +1,196 @@
+use std::io::Read;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use hyper::status::StatusClass;
+
+use rustc_serialize::json;
+
+use auth::{AccessToken, CacheKey, PersistentTokenCache, TokenCredential, AZURE_PUBLIC_CLOUD};
+use auth::azure_cli::AZURE_CLI_CLIENT_ID;
+use auth::cache::DEFAULT_REFRESH_SKEW;
+use auth::{claims_param, describe_oauth_error, oauth_error_code, post_form, redeem_refresh_token, resource_for};
+use auth::{send_form, token_url, user_token_from_response};
+use error::{Error, VaultResult};
+
+// seconds added to the polling interval when the token endpoint asks us to slow down
+const SLOW_DOWN_STEP: u64 = 5;
+
+/// What the user needs to sign in: a URL to open and a code to enter there.
+#[derive(RustcDecodable, Debug, Clone, PartialEq)]
+pub struct DeviceCode {
+  pub user_code: String,
+  pub device_code: String,
+  pub verification_url: String,
+  pub expires_in: u64,
+  pub interval: Option<u64>,
+  pub message: Option<String>,
+}
+
+/// Signs a user in with the device code flow: the user opens a URL on any
+/// device and enters a code, while this credential polls for the result. The
+/// refresh token from the sign-in is kept, and optionally stored in a
+/// `PersistentTokenCache`, so later tokens are issued without prompting again.
+pub struct DeviceCodeCredential {
+  authority_host: String,
+  tenant_id: String,
+  client_id: String,
+  persistent_cache: Option<PersistentTokenCache>,
+  refresh_token: Mutex<Option<String>>,
+  prompt: Box<Fn(&DeviceCode) + Send + Sync>,
+}
+
+impl DeviceCodeCredential {
+  /// Signs in to the user's home tenant as the Azure CLI public client, and
+  /// prints the prompt to stderr.
+  pub fn new() -> DeviceCodeCredential {
+    DeviceCodeCredential {
+      authority_host: AZURE_PUBLIC_CLOUD.to_string(),
+      tenant_id: "organizations".to_string(),
+      client_id: AZURE_CLI_CLIENT_ID.to_string(),
+      persistent_cache: None,
+      refresh_token: Mutex::new(None),
+      prompt: Box::new(|code: &DeviceCode| {
+        match code.message {
+          Some(ref message) => eprintln!("{}", message),
+          None => eprintln!("To sign in, open {} and enter the code {}", code.verification_url, code.user_code)
+        }
+      })
+    }
+  }
+
+  pub fn with_tenant(mut self, tenant_id: &str) -> DeviceCodeCredential {
+    self.tenant_id = tenant_id.to_string();
+    self
+  }
+
+  /// Uses another public client application registration.
+  pub fn with_client_id(mut self, client_id: &str) -> DeviceCodeCredential {
+    self.client_id = client_id.to_string();
+    self
+  }
+
+  /// Uses another authority, e.g. `https://login.chinacloudapi.cn`.
+  pub fn with_authority_host(mut self, authority_host: &str) -> DeviceCodeCredential {
+    self.authority_host = authority_host.to_string();
+    self
+  }
+
+  /// Shows the device code some other way than printing it to stderr.
+  pub fn with_prompt<F>(mut self, prompt: F) -> DeviceCodeCredential where F: Fn(&DeviceCode) + Send + Sync + 'static {
+    self.prompt = Box::new(prompt);
+    self
+  }
+
+  /// Keeps access and refresh tokens in an encrypted cache on disk, and starts
+  /// from the ones already there.
+  pub fn with_persistent_cache(mut self, cache: PersistentTokenCache) -> DeviceCodeCredential {
+    self.persistent_cache = Some(cache);
+    self
+  }
+
+  fn sign_in(&self, resource: &str, challenge: Option<&str>) -> VaultResult<(AccessToken, Option<String>)> {
+    let claims = try!(claims_param(challenge));
+    let url = format!("{}/{}/oauth2/devicecode", self.authority_host.trim_right_matches('/'), self.tenant_id);
+    let body = try!(post_form(url.as_ref(), &[("client_id", self.client_id.as_ref()),
+                                              ("resource", resource),
+                                              ("claims", claims.as_ref())]));
+    let code: DeviceCode = try!(json::decode(body.as_ref()));
+    (self.prompt)(&code);
+
+    let token_url = token_url(&self.authority_host, &self.tenant_id);
+    let mut interval = code.interval.unwrap_or(5);
+    let deadline = Instant::now() + Duration::from_secs(code.expires_in);
+    while Instant::now() < deadline {
+      thread::sleep(Duration::from_secs(interval));
+      let mut res = try!(send_form(token_url.as_ref(), &[("grant_type", "device_code"),
+                                                         ("client_id", self.client_id.as_ref()),
+                                                         ("code", code.device_code.as_ref()),
+                                                         ("resource", resource),
+                                                         ("claims", claims.as_ref())]));
+      let mut body = String::new();
+      try!(res.read_to_string(&mut body));
+      if res.status.class() == StatusClass::Success {
+        return user_token_from_response(body.as_ref());
+      }
+      match oauth_error_code(body.as_ref()).as_ref().map(|code| code.as_ref()) {
+        Some("authorization_pending") => (),
+        Some("slow_down") => interval += SLOW_DOWN_STEP,
+        _ => return Err(Error::Authentication(describe_oauth_error(&res.status.to_string(), body.as_ref()), None))
+      }
+    }
+    Err(Error::Authentication(format!("the device code expired before sign-in completed"), None))
+  }
+
+  fn cache_key(&self, resource: &str) -> CacheKey {
+    CacheKey {
+      tenant_id: self.tenant_id.clone(),
+      scope: format!("{}/.default", resource),
+      client_id: self.client_id.clone()
+    }
+  }
+
+  // a cached access token, else one from the refresh token, else a new sign-in;
+  // a claims challenge is never answered from the cache
+  fn token(&self, scopes: &[&str], use_cached: bool, challenge: Option<&str>) -> VaultResult<AccessToken> {
+    let resource = try!(resource_for(scopes));
+    // held throughout, so concurrent callers don't prompt the user twice
+    let mut refresh_token = self.refresh_token.lock().unwrap();
+
+    if let Some(ref cache) = self.persistent_cache {
+      if use_cached && challenge.is_none() {
+        if let Some(token) = try!(cache.access_token(&self.cache_key(resource.as_ref()))) {
+          if !token.expires_within(DEFAULT_REFRESH_SKEW) {
+            return Ok(token);
+          }
+        }
+      }
+      if refresh_token.is_none() {
+        *refresh_token = try!(cache.refresh_token(&self.tenant_id, &self.client_id));
+      }
+    }
+
+    let redeemed = match *refresh_token {
+      Some(ref stored) => {
+        match redeem_refresh_token(&self.authority_host, &self.tenant_id, &self.client_id, stored, resource.as_ref(), challenge) {
+          Ok((token, rotated)) => Some((token, rotated.or(Some(stored.clone())))),
+          Err(err) => {
+            debug!("stored refresh token was refused, signing in again: {}", err);
+            None
+          }
+        }
+      },
+      None => None
+    };
+    let (token, rotated) = match redeemed {
+      Some(redeemed) => redeemed,
+      None => try!(self.sign_in(resource.as_ref(), challenge))
+    };
+
+    if let Some(ref cache) = self.persistent_cache {
+      try!(cache.store_access_token(&self.cache_key(resource.as_ref()), &token));
+      if let Some(ref rotated) = rotated {
+        try!(cache.store_refresh_token(&self.tenant_id, &self.client_id, rotated));
+      }
+    }
+    if rotated.is_some() {
+      *refresh_token = rotated;
+    }
+    Ok(token)
+  }
+}
+
+impl TokenCredential for DeviceCodeCredential {
+  fn get_token(&self, scopes: &[&str]) -> VaultResult<AccessToken> {
+    self.token(scopes, true, None)
+  }
+
+  fn refresh_token(&self, scopes: &[&str]) -> VaultResult<AccessToken> {
+    self.token(scopes, false, None)
+  }
+
+  fn get_token_with_claims(&self, scopes: &[&str], claims: &str) -> VaultResult<AccessToken> {
+    self.token(scopes, false, Some(claims))
+  }
+}
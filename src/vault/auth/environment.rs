@@ -0,0 +1,66 @@
+use std::env;
+
+use auth::{AccessToken, CertificateCredential, ClientSecretCredential, TokenCredential};
+use auth::authority_host_from_env;
+use error::{Error, VaultResult};
+
+/// Authenticates a service principal described by environment variables:
+/// `AZURE_TENANT_ID` and `AZURE_CLIENT_ID`, with either `AZURE_CLIENT_SECRET` or
+/// `AZURE_CLIENT_CERTIFICATE_PATH` (and `AZURE_CLIENT_CERTIFICATE_PASSWORD` for
+/// a protected PFX). `AZURE_AUTHORITY_HOST` overrides the public cloud authority.
+pub struct EnvironmentCredential {
+  // why the environment does not describe a usable credential, if it doesn't
+  inner: Result<Box<TokenCredential>, Misconfigured>,
+}
+
+enum Misconfigured {
+  // the variables are not set, so the credential is unavailable
+  Unset(String),
+  // they are set, but to something unusable
+  Invalid(String),
+}
+
+impl EnvironmentCredential {
+  pub fn new() -> EnvironmentCredential {
+    EnvironmentCredential { inner: from_env() }
+  }
+
+  fn credential(&self) -> VaultResult<&TokenCredential> {
+    match self.inner {
+      Ok(ref credential) => Ok(&**credential),
+      Err(Misconfigured::Unset(ref reason)) => Err(Error::CredentialUnavailable(reason.clone())),
+      Err(Misconfigured::Invalid(ref reason)) => Err(Error::Authentication(reason.clone(), None))
+    }
+  }
+}
+
+impl TokenCredential for EnvironmentCredential {
+  fn get_token(&self, scopes: &[&str]) -> VaultResult<AccessToken> {
+    try!(self.credential()).get_token(scopes)
+  }
+
+  fn get_token_with_claims(&self, scopes: &[&str], claims: &str) -> VaultResult<AccessToken> {
+    try!(self.credential()).get_token_with_claims(scopes, claims)
+  }
+}
+
+fn from_env() -> Result<Box<TokenCredential>, Misconfigured> {
+  let (tenant_id, client_id) = match (env::var("AZURE_TENANT_ID"), env::var("AZURE_CLIENT_ID")) {
+    (Ok(tenant_id), Ok(client_id)) => (tenant_id, client_id),
+    _ => return Err(Misconfigured::Unset(format!("AZURE_TENANT_ID and AZURE_CLIENT_ID are not set")))
+  };
+  let authority_host = authority_host_from_env();
+
+  if let Ok(secret) = env::var("AZURE_CLIENT_SECRET") {
+    let credential = ClientSecretCredential::new(tenant_id.as_ref(), client_id.as_ref(), secret.as_ref());
+    return Ok(Box::new(credential.with_authority_host(authority_host.as_ref())));
+  }
+  if let Ok(path) = env::var("AZURE_CLIENT_CERTIFICATE_PATH") {
+    let password = env::var("AZURE_CLIENT_CERTIFICATE_PASSWORD").ok();
+    return match CertificateCredential::from_file(tenant_id.as_ref(), client_id.as_ref(), path, password.as_ref().map(|p| p.as_ref())) {
+      Ok(credential) => Ok(Box::new(credential.with_authority_host(authority_host.as_ref()))),
+      Err(err) => Err(Misconfigured::Invalid(format!("AZURE_CLIENT_CERTIFICATE_PATH could not be loaded: {}", err)))
+    };
+  }
+  Err(Misconfigured::Unset(format!("neither AZURE_CLIENT_SECRET nor AZURE_CLIENT_CERTIFICATE_PATH is set")))
+}
@@ -0,0 +1,151 @@
+use std::env;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use hyper::header::Headers;
+use hyper::method::Method;
+use hyper::status::StatusCode;
+
+use url;
+
+use auth::{AccessToken, TokenCredential};
+use auth::{read_token_body, resource_for, token_from_response};
+use error::{Error, VaultResult};
+use http::https_client;
+
+/// Token endpoint of the Azure Instance Metadata Service.
+pub const IMDS_ENDPOINT: &'static str = "http://169.254.169.254/metadata/identity/oauth2/token";
+
+const IMDS_API_VERSION: &'static str = "2018-02-01";
+const APP_SERVICE_API_VERSION: &'static str = "2019-08-01";
+
+// IMDS is only reachable from inside Azure; anywhere else the connect would
+// hang until the OS gives up, so check it answers first
+const IMDS_PROBE_TIMEOUT: u64 = 1;
+
+/// Selects one of several user-assigned identities attached to a resource.
+#[derive(Debug, Clone, PartialEq)]
+pub enum UserAssignedId {
+  ClientId(String),
+  ObjectId(String),
+  /// The ARM resource id, `/subscriptions/.../userAssignedIdentities/{name}`.
+  ResourceId(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Source {
+  Imds(String),
+  // App Service and Functions: IDENTITY_ENDPOINT and IDENTITY_HEADER
+  AppService(String, String),
+}
+
+/// Authenticates as the managed identity of the Azure resource the code runs on.
+#[derive(Debug)]
+pub struct ManagedIdentityCredential {
+  id: Option<UserAssignedId>,
+  source: Source,
+  // whether IMDS answered, checked before the first token request only
+  probed: Mutex<Option<Result<(), String>>>,
+}
+
+impl ManagedIdentityCredential {
+  /// The system-assigned identity, using the App Service endpoint when
+  /// `IDENTITY_ENDPOINT` and `IDENTITY_HEADER` are set, and IMDS otherwise.
+  pub fn new() -> ManagedIdentityCredential {
+    let source = match (env::var("IDENTITY_ENDPOINT"), env::var("IDENTITY_HEADER")) {
+      (Ok(endpoint), Ok(header)) => Source::AppService(endpoint, header),
+      _ => Source::Imds(IMDS_ENDPOINT.to_string())
+    };
+    ManagedIdentityCredential { id: None, source: source, probed: Mutex::new(None) }
+  }
+
+  pub fn user_assigned(id: UserAssignedId) -> ManagedIdentityCredential {
+    ManagedIdentityCredential { id: Some(id), ..ManagedIdentityCredential::new() }
+  }
+
+  /// Uses an IMDS compatible endpoint at another address.
+  pub fn with_imds_endpoint(mut self, endpoint: &str) -> ManagedIdentityCredential {
+    self.source = Source::Imds(endpoint.to_string());
+    self.probed = Mutex::new(None);
+    self
+  }
+
+  /// Uses an App Service style endpoint, authenticated with `X-IDENTITY-HEADER`.
+  pub fn with_app_service_endpoint(mut self, endpoint: &str, identity_header: &str) -> ManagedIdentityCredential {
+    self.source = Source::AppService(endpoint.to_string(), identity_header.to_string());
+    self
+  }
+
+  // other callers wait for the probe rather than making their own
+  fn probe_once(&self, endpoint: &str) -> VaultResult<()> {
+    let mut probed = self.probed.lock().unwrap();
+    if probed.is_none() {
+      *probed = Some(probe(endpoint));
+    }
+    match *probed {
+      Some(Err(ref reason)) => Err(Error::CredentialUnavailable(reason.clone())),
+      _ => Ok(())
+    }
+  }
+
+  fn token_url(&self, resource: &str) -> String {
+    let (endpoint, api_version, id_params) = match self.source {
+      Source::Imds(ref endpoint) => (endpoint, IMDS_API_VERSION, ["client_id", "object_id", "msi_res_id"]),
+      Source::AppService(ref endpoint, _) => (endpoint, APP_SERVICE_API_VERSION, ["client_id", "principal_id", "mi_res_id"])
+    };
+    let mut params = vec![("api-version", api_version), ("resource", resource)];
+    match self.id {
+      Some(UserAssignedId::ClientId(ref id)) => params.push((id_params[0], id.as_ref())),
+      Some(UserAssignedId::ObjectId(ref id)) => params.push((id_params[1], id.as_ref())),
+      Some(UserAssignedId::ResourceId(ref id)) => params.push((id_params[2], id.as_ref())),
+      None => ()
+    }
+    format!("{}?{}", endpoint, url::form_urlencoded::Serializer::new(String::new()).extend_pairs(params).finish())
+  }
+}
+
+impl TokenCredential for ManagedIdentityCredential {
+  fn get_token(&self, scopes: &[&str]) -> VaultResult<AccessToken> {
+    let resource = try!(resource_for(scopes));
+    let mut headers = Headers::new();
+    match self.source {
+      Source::Imds(ref endpoint) => {
+        try!(self.probe_once(endpoint));
+        headers.set_raw("Metadata", vec![b"true".to_vec()]);
+      },
+      Source::AppService(_, ref identity_header) => {
+        headers.set_raw("X-IDENTITY-HEADER", vec![identity_header.as_bytes().to_vec()]);
+      }
+    }
+
+    let url = self.token_url(resource.as_ref());
+    let client = try!(https_client());
+    let res = try!(client.request(Method::Get, &url[..]).headers(headers).send());
+    // IMDS answers 400 on a resource without the identity asked for
+    let no_identity = match self.source {
+      Source::Imds(_) => res.status == StatusCode::BadRequest,
+      Source::AppService(_, _) => false
+    };
+    let body = match read_token_body(res) {
+      Ok(body) => body,
+      Err(Error::Authentication(ref reason, _)) if no_identity => return Err(Error::CredentialUnavailable(reason.clone())),
+      Err(err) => return Err(err)
+    };
+    token_from_response(body.as_ref())
+  }
+}
+
+fn probe(endpoint: &str) -> Result<(), String> {
+  let unavailable = |reason: String| format!("managed identity endpoint {} is unavailable: {}", endpoint, reason);
+  let authority = endpoint.splitn(2, "://").last().unwrap_or("").split('/').next().unwrap_or("");
+  let address = if authority.contains(':') { authority.to_string() } else { format!("{}:80", authority) };
+  let addr = match address.to_socket_addrs().ok().and_then(|mut addrs| addrs.next()) {
+    Some(addr) => addr,
+    None => return Err(unavailable(format!("cannot resolve {}", address)))
+  };
+  match TcpStream::connect_timeout(&addr, Duration::from_secs(IMDS_PROBE_TIMEOUT)) {
+    Ok(_) => Ok(()),
+    Err(err) => Err(unavailable(err.to_string()))
+  }
+}
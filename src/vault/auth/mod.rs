@@ -0,0 +1,255 @@
+// Credentials that obtain Azure Active Directory tokens for Key Vault.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::OpenOptions;
+use std::io::Read;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hyper::client::Response;
+use hyper::header::{ContentType, Headers};
+use hyper::method::Method;
+use hyper::mime::Mime;
+use hyper::status::StatusClass;
+
+use rustc_serialize::json::{self, Json};
+
+use url;
+
+use error::{Error, VaultResult};
+use http::https_client;
+
+pub mod azure_cli;
+pub mod cache;
+pub mod certificate;
+pub mod client_secret;
+pub mod default;
+pub mod device_code;
+pub mod environment;
+pub mod managed_identity;
+pub mod persistent;
+pub mod workload_identity;
+
+pub use self::azure_cli::AzureCliCredential;
+pub use self::cache::{CacheKey, CachedTokenCredential, TokenCache};
+pub use self::certificate::CertificateCredential;
+pub use self::client_secret::ClientSecretCredential;
+pub use self::default::{ChainedTokenCredential, DefaultAzureCredential};
+pub use self::device_code::{DeviceCode, DeviceCodeCredential};
+pub use self::environment::EnvironmentCredential;
+pub use self::managed_identity::{ManagedIdentityCredential, UserAssignedId};
+pub use self::persistent::PersistentTokenCache;
+pub use self::workload_identity::WorkloadIdentityCredential;
+
+/// Authority host of the Azure public cloud.
+pub const AZURE_PUBLIC_CLOUD: &'static str = "https://login.microsoftonline.com";
+
+/// Client capabilities advertised with every token request. `cp1` says the
+/// client handles claims challenges, so Azure AD may issue tokens that
+/// Continuous Access Evaluation revokes before they expire.
+pub const CLIENT_CAPABILITIES: &'static [&'static str] = &["cp1"];
+
+// client_assertion_type for signed JWT assertions
+const JWT_BEARER: &'static str = "urn:ietf:params:oauth:client-assertion-type:jwt-bearer";
+
+/// A bearer token and the time, in seconds since the Unix epoch, at which it expires.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessToken {
+  pub token: String,
+  pub expires_on: i64,
+}
+
+impl AccessToken {
+  pub fn new(token: String, expires_on: i64) -> AccessToken {
+    AccessToken { token: token, expires_on: expires_on }
+  }
+
+  /// True when the token expires within `seconds` from now.
+  pub fn expires_within(&self, seconds: i64) -> bool {
+    self.expires_on - seconds <= now()
+  }
+}
+
+/// Something that can obtain an access token for a set of scopes, such as
+/// `https://vault.azure.net/.default`.
+pub trait TokenCredential: Send + Sync {
+  fn get_token(&self, scopes: &[&str]) -> VaultResult<AccessToken>;
+
+  /// Like `get_token`, but never answers from a cache. Called when the vault
+  /// rejects a token it was given.
+  fn refresh_token(&self, scopes: &[&str]) -> VaultResult<AccessToken> {
+    self.get_token(scopes)
+  }
+
+  /// Like `refresh_token`, for a token that satisfies `claims`: the decoded JSON
+  /// of a vault's `insufficient_claims` challenge. Credentials whose token
+  /// endpoint takes no claims, such as managed identity, fall back to
+  /// `refresh_token`.
+  fn get_token_with_claims(&self, scopes: &[&str], _claims: &str) -> VaultResult<AccessToken> {
+    self.refresh_token(scopes)
+  }
+}
+
+// AZURE_AUTHORITY_HOST, or the public cloud
+fn authority_host_from_env() -> String {
+  env::var("AZURE_AUTHORITY_HOST").unwrap_or(AZURE_PUBLIC_CLOUD.to_string())
+}
+
+/// Directory for the CLI's files: `$XDG_CONFIG_HOME/vault`, `~/.config/vault`,
+/// or `%APPDATA%\vault` on Windows.
+pub fn config_dir() -> Option<PathBuf> {
+  let base = if cfg!(windows) {
+    env::var("APPDATA").ok().map(PathBuf::from)
+  } else {
+    env::var("XDG_CONFIG_HOME").ok().map(PathBuf::from)
+      .or_else(|| env::home_dir().map(|home| home.join(".config")))
+  };
+  base.map(|base| base.join("vault"))
+}
+
+// options for creating a file only the current user can read
+#[cfg(unix)]
+fn private_file_options() -> OpenOptions {
+  use std::os::unix::fs::OpenOptionsExt;
+  let mut options = OpenOptions::new();
+  options.write(true).create(true).truncate(true).mode(0o600);
+  options
+}
+
+#[cfg(not(unix))]
+fn private_file_options() -> OpenOptions {
+  let mut options = OpenOptions::new();
+  options.write(true).create(true).truncate(true);
+  options
+}
+
+// seconds since the Unix epoch
+pub fn now() -> i64 {
+  SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs() as i64).unwrap_or(0)
+}
+
+// the v1 token endpoint takes a resource rather than scopes, so turn
+// `https://vault.azure.net/.default` back into `https://vault.azure.net`
+fn resource_for(scopes: &[&str]) -> VaultResult<String> {
+  if scopes.len() == 1 {
+    Ok(scopes[0].trim_right_matches("/.default").to_string())
+  } else {
+    Err(Error::InvalidArgument(format!("expected exactly one scope, got {}", scopes.len())))
+  }
+}
+
+fn token_url(authority_host: &str, tenant_id: &str) -> String {
+  format!("{}/{}/oauth2/token", authority_host.trim_right_matches('/'), tenant_id)
+}
+
+// the `claims` request parameter: the client capabilities, merged into the
+// claims a challenge asked for
+fn claims_param(challenge: Option<&str>) -> VaultResult<String> {
+  let mut claims = match challenge {
+    Some(challenge) => match Json::from_str(challenge) {
+      Ok(Json::Object(claims)) => claims,
+      _ => return Err(Error::Authentication(format!("claims challenge is not a JSON object: {}", challenge), None))
+    },
+    None => BTreeMap::new()
+  };
+  {
+    let access_token = claims.entry("access_token".to_string()).or_insert_with(|| Json::Object(BTreeMap::new()));
+    let access_token = match *access_token {
+      Json::Object(ref mut access_token) => access_token,
+      _ => return Err(Error::Authentication(format!("claims challenge has a malformed access_token claim"), None))
+    };
+    let mut xms_cc = BTreeMap::new();
+    xms_cc.insert("values".to_string(), Json::Array(CLIENT_CAPABILITIES.iter().map(|cap| Json::String(cap.to_string())).collect()));
+    access_token.insert("xms_cc".to_string(), Json::Object(xms_cc));
+  }
+  Ok(Json::Object(claims).to_string())
+}
+
+// successful token endpoint response; v1 sends the numbers as strings
+#[derive(RustcDecodable, Debug, Clone)]
+struct TokenResponse {
+  access_token: String,
+  expires_in: Option<i64>,
+  expires_on: Option<i64>,
+  refresh_token: Option<String>,
+}
+
+// POSTs a form to a token endpoint and decodes the token it returns
+fn request_token(url: &str, params: &[(&str, &str)]) -> VaultResult<AccessToken> {
+  let body = try!(post_form(url, params));
+  token_from_response(body.as_ref())
+}
+
+fn token_from_response(body: &str) -> VaultResult<AccessToken> {
+  user_token_from_response(body).map(|(token, _)| token)
+}
+
+// the access token and, for user sign-ins, the refresh token that came with it
+fn user_token_from_response(body: &str) -> VaultResult<(AccessToken, Option<String>)> {
+  let response: TokenResponse = try!(json::decode(body));
+  let expires_on = match (response.expires_on, response.expires_in) {
+    (Some(expires_on), _) => expires_on,
+    (None, Some(expires_in)) => now() + expires_in,
+    (None, None) => return Err(Error::Authentication(format!("token response has no expiry"), None))
+  };
+  Ok((AccessToken::new(response.access_token, expires_on), response.refresh_token))
+}
+
+// exchanges a refresh token from a user sign-in for a token to `resource`,
+// satisfying a claims challenge if there is one
+fn redeem_refresh_token(authority_host: &str, tenant_id: &str, client_id: &str, refresh_token: &str, resource: &str, challenge: Option<&str>) -> VaultResult<(AccessToken, Option<String>)> {
+  let url = token_url(authority_host, tenant_id);
+  let claims = try!(claims_param(challenge));
+  let body = try!(post_form(url.as_ref(), &[("grant_type", "refresh_token"),
+                                            ("client_id", client_id),
+                                            ("refresh_token", refresh_token),
+                                            ("resource", resource),
+                                            ("claims", claims.as_ref())]));
+  user_token_from_response(body.as_ref())
+}
+
+// POSTs a form and returns the body of a successful response; OAuth2 errors
+// (`{"error": ..., "error_description": ...}`) become `Error::Authentication`
+fn post_form(url: &str, params: &[(&str, &str)]) -> VaultResult<String> {
+  let res = try!(send_form(url, params));
+  read_token_body(res)
+}
+
+fn send_form(url: &str, params: &[(&str, &str)]) -> VaultResult<Response> {
+  let form = url::form_urlencoded::Serializer::new(String::new()).extend_pairs(params).finish();
+  let mut headers = Headers::new();
+  let form_mime: Mime = "application/x-www-form-urlencoded".parse().unwrap();
+  headers.set(ContentType(form_mime));
+
+  let client = try!(https_client());
+  Ok(try!(client.request(Method::Post, url).headers(headers).body(&form[..]).send()))
+}
+
+fn read_token_body(mut res: Response) -> VaultResult<String> {
+  let mut body = String::new();
+  try!(res.read_to_string(&mut body));
+  match res.status.class() {
+    StatusClass::Success => Ok(body),
+    _ => Err(Error::Authentication(describe_oauth_error(&res.status.to_string(), body.as_ref()), None))
+  }
+}
+
+// the `error` code of an OAuth2 error body, e.g. `authorization_pending`
+fn oauth_error_code(body: &str) -> Option<String> {
+  Json::from_str(body).ok()
+    .and_then(|json| json.find("error").and_then(|error| error.as_string()).map(|error| error.to_string()))
+}
+
+fn describe_oauth_error(status: &str, body: &str) -> String {
+  let json = Json::from_str(body).ok();
+  let field = |name: &str| json.as_ref()
+    .and_then(|json| json.find(name))
+    .and_then(|value| value.as_string())
+    .map(|value| value.to_string());
+  match (field("error"), field("error_description")) {
+    (Some(error), Some(description)) => format!("{}: {} ({})", status, error, description),
+    (Some(error), None) => format!("{}: {}", status, error),
+    _ => format!("{}: {}", status, body)
+  }
+}
@@ -0,0 +1,327 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, ErrorKind, Read, Write};
+use std::path::PathBuf;
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use openssl::error::ErrorStack;
+use openssl::hash::MessageDigest;
+use openssl::pkcs5::pbkdf2_hmac;
+use openssl::rand::rand_bytes;
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+
+use rustc_serialize::json;
+
+use auth::{AccessToken, CacheKey};
+use auth::{config_dir, private_file_options};
+use error::{Error, VaultResult};
+
+// file header, also authenticated as associated data
+const MAGIC: &'static [u8] = b"VTC1";
+const KEYFILE_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+const PBKDF2_ITERATIONS: usize = 10000;
+
+// how long to wait for another process to release the cache, and the age at
+// which a lock left behind by a crashed process is broken
+const LOCK_TIMEOUT: u64 = 10;
+const STALE_LOCK_AGE: u64 = 30;
+
+#[derive(RustcEncodable, RustcDecodable, Debug, Clone, PartialEq)]
+struct CachedAccessToken {
+  tenant_id: String,
+  scope: String,
+  client_id: String,
+  token: String,
+  expires_on: i64,
+}
+
+#[derive(RustcEncodable, RustcDecodable, Debug, Clone, PartialEq)]
+struct CachedRefreshToken {
+  tenant_id: String,
+  client_id: String,
+  token: String,
+}
+
+#[derive(RustcEncodable, RustcDecodable, Debug, Clone, PartialEq, Default)]
+struct CacheContents {
+  access_tokens: Vec<CachedAccessToken>,
+  refresh_tokens: Vec<CachedRefreshToken>,
+}
+
+/// Access and refresh tokens kept on disk between runs of the CLI, encrypted
+/// with AES-256-GCM under a key derived from a random keyfile next to the
+/// cache. Readers and writers in other processes are kept out with a lock file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PersistentTokenCache {
+  dir: PathBuf,
+}
+
+impl PersistentTokenCache {
+  /// A cache in `dir`, which is created when the first token is stored.
+  pub fn new(dir: PathBuf) -> PersistentTokenCache {
+    PersistentTokenCache { dir: dir }
+  }
+
+  /// A cache in the user's config directory; see `auth::config_dir`.
+  pub fn in_config_dir() -> Option<PersistentTokenCache> {
+    config_dir().map(PersistentTokenCache::new)
+  }
+
+  pub fn access_token(&self, key: &CacheKey) -> VaultResult<Option<AccessToken>> {
+    let contents = try!(self.read());
+    Ok(contents.access_tokens.into_iter()
+      .find(|cached| cached.tenant_id == key.tenant_id && cached.scope == key.scope && cached.client_id == key.client_id)
+      .map(|cached| AccessToken::new(cached.token, cached.expires_on)))
+  }
+
+  pub fn store_access_token(&self, key: &CacheKey, token: &AccessToken) -> VaultResult<()> {
+    self.update(|contents| {
+      contents.access_tokens.retain(|cached| {
+        !(cached.tenant_id == key.tenant_id && cached.scope == key.scope && cached.client_id == key.client_id)
+      });
+      contents.access_tokens.push(CachedAccessToken {
+        tenant_id: key.tenant_id.clone(),
+        scope: key.scope.clone(),
+        client_id: key.client_id.clone(),
+        token: token.token.clone(),
+        expires_on: token.expires_on
+      });
+    })
+  }
+
+  pub fn refresh_token(&self, tenant_id: &str, client_id: &str) -> VaultResult<Option<String>> {
+    let contents = try!(self.read());
+    Ok(contents.refresh_tokens.into_iter()
+      .find(|cached| cached.tenant_id == tenant_id && cached.client_id == client_id)
+      .map(|cached| cached.token))
+  }
+
+  pub fn store_refresh_token(&self, tenant_id: &str, client_id: &str, token: &str) -> VaultResult<()> {
+    self.update(|contents| {
+      contents.refresh_tokens.retain(|cached| !(cached.tenant_id == tenant_id && cached.client_id == client_id));
+      contents.refresh_tokens.push(CachedRefreshToken {
+        tenant_id: tenant_id.to_string(),
+        client_id: client_id.to_string(),
+        token: token.to_string()
+      });
+    })
+  }
+
+  /// Deletes every cached token, along with the keyfile they were encrypted under.
+  pub fn clear(&self) -> VaultResult<()> {
+    if !self.dir.exists() {
+      return Ok(());
+    }
+    let _lock = try!(self.lock());
+    for path in [self.cache_path(), self.keyfile_path()].iter() {
+      match fs::remove_file(path) {
+        Err(ref err) if err.kind() == ErrorKind::NotFound => (),
+        result => try!(result)
+      }
+    }
+    Ok(())
+  }
+
+  fn cache_path(&self) -> PathBuf {
+    self.dir.join("tokens.bin")
+  }
+
+  fn keyfile_path(&self) -> PathBuf {
+    self.dir.join("tokens.key")
+  }
+
+  fn lock_path(&self) -> PathBuf {
+    self.dir.join("tokens.lock")
+  }
+
+  fn read(&self) -> VaultResult<CacheContents> {
+    if !self.cache_path().exists() {
+      return Ok(CacheContents::default());
+    }
+    let _lock = try!(self.lock());
+    self.load()
+  }
+
+  fn update<F>(&self, change: F) -> VaultResult<()> where F: FnOnce(&mut CacheContents) {
+    try!(fs::create_dir_all(&self.dir));
+    let _lock = try!(self.lock());
+    let mut contents = try!(self.load());
+    change(&mut contents);
+    self.save(&contents)
+  }
+
+  // a cache that can't be decrypted, e.g. because the keyfile was replaced,
+  // is treated as empty so the user simply signs in again
+  fn load(&self) -> VaultResult<CacheContents> {
+    let mut data = Vec::new();
+    match File::open(self.cache_path()) {
+      Ok(mut file) => try!(file.read_to_end(&mut data)),
+      Err(ref err) if err.kind() == ErrorKind::NotFound => return Ok(CacheContents::default()),
+      Err(err) => return Err(Error::Io(err))
+    };
+    // reading never creates the keyfile; a cache without one can't be decrypted
+    let keyfile = match try!(self.read_keyfile()) {
+      Some(keyfile) => keyfile,
+      None => return Ok(CacheContents::default())
+    };
+    match decrypt(&keyfile[..], &data[..]).and_then(|plain| String::from_utf8(plain).ok()) {
+      Some(plain) => Ok(json::decode(plain.as_ref()).unwrap_or_default()),
+      None => {
+        warn!("ignoring token cache {} that could not be decrypted", self.cache_path().display());
+        Ok(CacheContents::default())
+      }
+    }
+  }
+
+  fn save(&self, contents: &CacheContents) -> VaultResult<()> {
+    let keyfile = try!(self.keyfile());
+    let data = try!(encrypt(&keyfile[..], json::encode(contents).unwrap().as_bytes()).map_err(|err| {
+      Error::Io(io::Error::new(ErrorKind::Other, format!("could not encrypt token cache: {}", err)))
+    }));
+    // written aside and renamed, so a crash never leaves half a cache behind
+    let staging = self.dir.join("tokens.bin.tmp");
+    {
+      let mut file = try!(private_file_options().open(&staging));
+      try!(file.write_all(&data[..]));
+    }
+    Ok(try!(fs::rename(staging, self.cache_path())))
+  }
+
+  fn read_keyfile(&self) -> VaultResult<Option<Vec<u8>>> {
+    let mut keyfile = Vec::new();
+    match File::open(self.keyfile_path()) {
+      Ok(mut file) => {
+        try!(file.read_to_end(&mut keyfile));
+        Ok(Some(keyfile))
+      },
+      Err(ref err) if err.kind() == ErrorKind::NotFound => Ok(None),
+      Err(err) => Err(Error::Io(err))
+    }
+  }
+
+  // the random keyfile, created when the first token is saved; only called
+  // under the lock
+  fn keyfile(&self) -> VaultResult<Vec<u8>> {
+    if let Some(keyfile) = try!(self.read_keyfile()) {
+      return Ok(keyfile);
+    }
+    let mut keyfile = vec![0u8; KEYFILE_LEN];
+    try!(rand_bytes(&mut keyfile[..]).map_err(openssl_error));
+    try!(fs::create_dir_all(&self.dir));
+    let mut file = try!(private_file_options().open(self.keyfile_path()));
+    try!(file.write_all(&keyfile[..]));
+    Ok(keyfile)
+  }
+
+  fn lock(&self) -> VaultResult<CacheLock> {
+    let path = self.lock_path();
+    let deadline = Instant::now() + Duration::from_secs(LOCK_TIMEOUT);
+    loop {
+      match OpenOptions::new().write(true).create_new(true).open(&path) {
+        Ok(_) => return Ok(CacheLock { path: path }),
+        Err(ref err) if err.kind() == ErrorKind::AlreadyExists => {
+          if is_stale(&path) {
+            try!(break_lock(&path));
+            continue;
+          }
+          if Instant::now() > deadline {
+            return Err(Error::Io(io::Error::new(ErrorKind::TimedOut, format!("token cache {} is locked by another process", path.display()))));
+          }
+          thread::sleep(Duration::from_millis(50));
+        },
+        Err(err) => return Err(Error::Io(err))
+      }
+    }
+  }
+}
+
+// removes the lock file when dropped
+struct CacheLock {
+  path: PathBuf,
+}
+
+impl Drop for CacheLock {
+  fn drop(&mut self) {
+    let _ = fs::remove_file(&self.path);
+  }
+}
+
+// Moves a stale lock aside before deleting it. The rename takes the lock file
+// from every other process trying to break it, and the lock it took is checked
+// again: if it is not the stale one but a lock taken since, it is put back
+// unless yet another process has locked the cache in the meantime.
+fn break_lock(path: &PathBuf) -> VaultResult<()> {
+  let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|since| since.subsec_nanos()).unwrap_or(0);
+  let aside = path.with_extension(format!("lock.{}.{}", process::id(), nanos));
+  match fs::rename(path, &aside) {
+    Ok(()) => (),
+    // another process broke it first
+    Err(ref err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+    Err(err) => return Err(Error::Io(err))
+  }
+  if is_stale(&aside) {
+    warn!("breaking stale token cache lock {}", path.display());
+  } else {
+    match fs::hard_link(&aside, path) {
+      Ok(()) => (),
+      Err(ref err) if err.kind() == ErrorKind::AlreadyExists => (),
+      Err(err) => {
+        let _ = fs::remove_file(&aside);
+        return Err(Error::Io(err));
+      }
+    }
+  }
+  Ok(try!(fs::remove_file(&aside)))
+}
+
+fn is_stale(path: &PathBuf) -> bool {
+  fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+    .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+    .map(|age| age > Duration::from_secs(STALE_LOCK_AGE))
+    .unwrap_or(false)
+}
+
+fn openssl_error(err: ErrorStack) -> Error {
+  Error::Io(io::Error::new(ErrorKind::Other, err.to_string()))
+}
+
+fn derive_key(keyfile: &[u8], salt: &[u8]) -> Result<[u8; 32], ErrorStack> {
+  let mut key = [0u8; 32];
+  try!(pbkdf2_hmac(keyfile, salt, PBKDF2_ITERATIONS, MessageDigest::sha256(), &mut key));
+  Ok(key)
+}
+
+// MAGIC | salt | nonce | tag | ciphertext
+fn encrypt(keyfile: &[u8], plain: &[u8]) -> Result<Vec<u8>, ErrorStack> {
+  let mut salt = [0u8; SALT_LEN];
+  let mut nonce = [0u8; NONCE_LEN];
+  try!(rand_bytes(&mut salt));
+  try!(rand_bytes(&mut nonce));
+  let key = try!(derive_key(keyfile, &salt));
+  let mut tag = [0u8; TAG_LEN];
+  let ciphertext = try!(encrypt_aead(Cipher::aes_256_gcm(), &key, Some(&nonce), MAGIC, plain, &mut tag));
+
+  let mut data = Vec::with_capacity(MAGIC.len() + SALT_LEN + NONCE_LEN + TAG_LEN + ciphertext.len());
+  data.extend_from_slice(MAGIC);
+  data.extend_from_slice(&salt);
+  data.extend_from_slice(&nonce);
+  data.extend_from_slice(&tag);
+  data.extend_from_slice(&ciphertext[..]);
+  Ok(data)
+}
+
+fn decrypt(keyfile: &[u8], data: &[u8]) -> Option<Vec<u8>> {
+  let header = MAGIC.len() + SALT_LEN + NONCE_LEN + TAG_LEN;
+  if data.len() < header || &data[..MAGIC.len()] != MAGIC {
+    return None;
+  }
+  let (salt, rest) = data[MAGIC.len()..].split_at(SALT_LEN);
+  let (nonce, rest) = rest.split_at(NONCE_LEN);
+  let (tag, ciphertext) = rest.split_at(TAG_LEN);
+  derive_key(keyfile, salt).ok()
+    .and_then(|key| decrypt_aead(Cipher::aes_256_gcm(), &key, Some(nonce), MAGIC, ciphertext, tag).ok())
+}
@@ -0,0 +1,99 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use auth::{AccessToken, TokenCredential, JWT_BEARER};
+use auth::{authority_host_from_env, claims_param, request_token, resource_for, token_url};
+use error::{Error, VaultResult};
+
+// re-read the token file at least this often, even if its timestamp is unchanged
+const MAX_ASSERTION_AGE: u64 = 300;
+
+/// Exchanges a federated token, such as a Kubernetes service account token, for
+/// an Azure Active Directory token. The token file is re-read whenever it is
+/// replaced, so tokens the kubelet rotates are picked up without a restart.
+#[derive(Debug)]
+pub struct WorkloadIdentityCredential {
+  authority_host: String,
+  tenant_id: String,
+  client_id: String,
+  token_file: PathBuf,
+  // the file's modification time, when it was read, and its contents
+  assertion: Mutex<Option<(SystemTime, Instant, String)>>,
+}
+
+impl WorkloadIdentityCredential {
+  pub fn new(tenant_id: &str, client_id: &str, token_file: PathBuf) -> WorkloadIdentityCredential {
+    WorkloadIdentityCredential {
+      authority_host: authority_host_from_env(),
+      tenant_id: tenant_id.to_string(),
+      client_id: client_id.to_string(),
+      token_file: token_file,
+      assertion: Mutex::new(None)
+    }
+  }
+
+  /// Reads `AZURE_TENANT_ID`, `AZURE_CLIENT_ID` and `AZURE_FEDERATED_TOKEN_FILE`,
+  /// as set by the workload identity webhook.
+  pub fn from_env() -> VaultResult<WorkloadIdentityCredential> {
+    match (env::var("AZURE_TENANT_ID"), env::var("AZURE_CLIENT_ID"), env::var("AZURE_FEDERATED_TOKEN_FILE")) {
+      (Ok(tenant_id), Ok(client_id), Ok(token_file)) => {
+        Ok(WorkloadIdentityCredential::new(tenant_id.as_ref(), client_id.as_ref(), PathBuf::from(token_file)))
+      },
+      _ => Err(Error::CredentialUnavailable(format!("AZURE_TENANT_ID, AZURE_CLIENT_ID and AZURE_FEDERATED_TOKEN_FILE are not all set")))
+    }
+  }
+
+  /// Uses another authority, e.g. `https://login.chinacloudapi.cn`.
+  pub fn with_authority_host(mut self, authority_host: &str) -> WorkloadIdentityCredential {
+    self.authority_host = authority_host.to_string();
+    self
+  }
+
+  fn read_assertion(&self) -> VaultResult<String> {
+    let modified = try!(fs::metadata(&self.token_file).and_then(|metadata| metadata.modified()));
+    let mut cached = self.assertion.lock().unwrap();
+    if let Some((read_modified, read_at, ref assertion)) = *cached {
+      if read_modified == modified && read_at.elapsed() < Duration::from_secs(MAX_ASSERTION_AGE) {
+        return Ok(assertion.clone());
+      }
+    }
+
+    debug!("reading federated token from {}", self.token_file.display());
+    let mut assertion = String::new();
+    let mut file = try!(File::open(&self.token_file));
+    try!(file.read_to_string(&mut assertion));
+    let assertion = assertion.trim().to_string();
+    if assertion.is_empty() {
+      return Err(Error::Authentication(format!("federated token file {} is empty", self.token_file.display()), None));
+    }
+    *cached = Some((modified, Instant::now(), assertion.clone()));
+    Ok(assertion)
+  }
+
+  fn token(&self, scopes: &[&str], challenge: Option<&str>) -> VaultResult<AccessToken> {
+    let resource = try!(resource_for(scopes));
+    let claims = try!(claims_param(challenge));
+    let assertion = try!(self.read_assertion());
+    let url = token_url(&self.authority_host, &self.tenant_id);
+    request_token(url.as_ref(), &[("grant_type", "client_credentials"),
+                                  ("client_id", self.client_id.as_ref()),
+                                  ("client_assertion_type", JWT_BEARER),
+                                  ("client_assertion", assertion.as_ref()),
+                                  ("resource", resource.as_ref()),
+                                  ("claims", claims.as_ref())])
+  }
+}
+
+impl TokenCredential for WorkloadIdentityCredential {
+  fn get_token(&self, scopes: &[&str]) -> VaultResult<AccessToken> {
+    self.token(scopes, None)
+  }
+
+  fn get_token_with_claims(&self, scopes: &[&str], claims: &str) -> VaultResult<AccessToken> {
+    self.token(scopes, Some(claims))
+  }
+}
@@ -0,0 +1,61 @@
+// The national clouds and vault flavours a client can talk to.
+
+use auth::AZURE_PUBLIC_CLOUD;
+
+/// Where a cloud's vaults are and who issues their tokens. The authority host is
+/// for the credential, e.g. `ClientSecretCredential::with_authority_host`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CloudProfile {
+  /// Azure Active Directory authority, e.g. `https://login.microsoftonline.com`
+  pub authority_host: String,
+  /// Suffix of vault host names, e.g. `vault.azure.net`
+  pub vault_dns_suffix: String,
+  /// Scope of tokens for the vaults, e.g. `https://vault.azure.net/.default`
+  pub scope: String,
+}
+
+impl CloudProfile {
+  pub fn new(authority_host: &str, vault_dns_suffix: &str, scope: &str) -> CloudProfile {
+    CloudProfile {
+      authority_host: authority_host.to_string(),
+      vault_dns_suffix: vault_dns_suffix.to_string(),
+      scope: scope.to_string()
+    }
+  }
+
+  pub fn public() -> CloudProfile {
+    CloudProfile::new(AZURE_PUBLIC_CLOUD, "vault.azure.net", "https://vault.azure.net/.default")
+  }
+
+  /// Azure operated by 21Vianet.
+  pub fn china() -> CloudProfile {
+    CloudProfile::new("https://login.chinacloudapi.cn", "vault.azure.cn", "https://vault.azure.cn/.default")
+  }
+
+  pub fn us_government() -> CloudProfile {
+    CloudProfile::new("https://login.microsoftonline.us", "vault.usgovcloudapi.net", "https://vault.usgovcloudapi.net/.default")
+  }
+
+  pub fn germany() -> CloudProfile {
+    CloudProfile::new("https://login.microsoftonline.de", "vault.microsoftazure.de", "https://vault.microsoftazure.de/.default")
+  }
+
+  /// Managed HSM pools in the public cloud, `https://<name>.managedhsm.azure.net`.
+  pub fn managed_hsm() -> CloudProfile {
+    CloudProfile::new(AZURE_PUBLIC_CLOUD, "managedhsm.azure.net", "https://managedhsm.azure.net/.default")
+  }
+
+  /// The profile whose vaults are served from `host`, if it is a known one.
+  pub fn for_host(host: &str) -> Option<CloudProfile> {
+    let profiles = [CloudProfile::public(), CloudProfile::china(), CloudProfile::us_government(),
+                    CloudProfile::germany(), CloudProfile::managed_hsm()];
+    profiles.iter()
+      .find(|profile| host.ends_with(&format!(".{}", profile.vault_dns_suffix)[..]))
+      .cloned()
+  }
+
+  /// The URL of the vault `name` in this cloud.
+  pub fn vault_url(&self, name: &str) -> String {
+    format!("https://{}.{}", name, self.vault_dns_suffix)
+  }
+}
@@ -0,0 +1,79 @@
+// Just enough of a DER reader to pull keys out of PKCS#1, PKCS#8 and SEC1 structures.
+
+pub const INTEGER: u8 = 0x02;
+pub const BIT_STRING: u8 = 0x03;
+pub const OCTET_STRING: u8 = 0x04;
+pub const OBJECT_IDENTIFIER: u8 = 0x06;
+pub const SEQUENCE: u8 = 0x30;
+
+// context specific, constructed tags [0] and [1]
+pub const CONTEXT_0: u8 = 0xA0;
+pub const CONTEXT_1: u8 = 0xA1;
+
+pub struct DerReader<'a> {
+  data: &'a [u8]
+}
+
+impl<'a> DerReader<'a> {
+  pub fn new(data: &'a [u8]) -> DerReader<'a> {
+    DerReader { data: data }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.data.is_empty()
+  }
+
+  // reads the next element, returning its tag and contents
+  pub fn read_any(&mut self) -> Result<(u8, &'a [u8]), String> {
+    if self.data.len() < 2 {
+      return Err(format!("truncated DER element"));
+    }
+    let tag = self.data[0];
+    let (length, header) = match self.data[1] {
+      short if short < 0x80 => (short as usize, 2),
+      long => {
+        let count = (long & 0x7f) as usize;
+        if count == 0 || count > 4 || self.data.len() < 2 + count {
+          return Err(format!("unsupported DER length encoding"));
+        }
+        let length = self.data[2..2 + count].iter().fold(0usize, |acc, byte| (acc << 8) | *byte as usize);
+        (length, 2 + count)
+      }
+    };
+    if self.data.len() < header + length {
+      return Err(format!("truncated DER element"));
+    }
+    let contents = &self.data[header..header + length];
+    self.data = &self.data[header + length..];
+    Ok((tag, contents))
+  }
+
+  pub fn read(&mut self, expected: u8) -> Result<&'a [u8], String> {
+    let (tag, contents) = try!(self.read_any());
+    if tag == expected {
+      Ok(contents)
+    } else {
+      Err(format!("expected DER tag {:#x}, found {:#x}", expected, tag))
+    }
+  }
+
+  pub fn read_sequence(&mut self) -> Result<DerReader<'a>, String> {
+    self.read(SEQUENCE).map(DerReader::new)
+  }
+
+  // reads an INTEGER as unsigned big-endian bytes without leading zeros
+  pub fn read_unsigned(&mut self) -> Result<&'a [u8], String> {
+    let contents = try!(self.read(INTEGER));
+    let start = contents.iter().position(|byte| *byte != 0).unwrap_or(contents.len());
+    Ok(&contents[start..])
+  }
+
+  // reads a BIT STRING, dropping the unused-bits prefix
+  pub fn read_bit_string(&mut self) -> Result<&'a [u8], String> {
+    let contents = try!(self.read(BIT_STRING));
+    match contents.split_first() {
+      Some((&0, bits)) => Ok(bits),
+      _ => Err(format!("unsupported BIT STRING padding"))
+    }
+  }
+}
@@ -0,0 +1,206 @@
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+
+use hyper::Error as HttpError;
+use hyper::status::StatusCode;
+
+use rustc_serialize::json::{Json, DecoderError};
+use rustc_serialize::base64::FromBase64Error;
+
+/// Result type returned by every vault operation.
+pub type VaultResult<T> = Result<T, Error>;
+
+/// A nested `innererror` from a Key Vault error body.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InnerError {
+  pub code: String,
+  pub inner_error: Option<Box<InnerError>>,
+}
+
+impl InnerError {
+  fn from_json(json: &Json) -> Option<Box<InnerError>> {
+    json.find("code").and_then(|code| code.as_string()).map(|code| {
+      Box::new(InnerError {
+        code: code.to_string(),
+        inner_error: json.find("innererror").and_then(InnerError::from_json)
+      })
+    })
+  }
+}
+
+/// The error Key Vault reported for a rejected request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServiceError {
+  /// HTTP status of the response.
+  pub status: StatusCode,
+  /// Value of `error.code`, e.g. `KeyNotFound`.
+  pub code: Option<String>,
+  /// Value of `error.message`.
+  pub message: Option<String>,
+  /// The `error.innererror` chain.
+  pub inner_error: Option<Box<InnerError>>,
+  /// The `x-ms-request-id` response header, to quote in support tickets.
+  pub request_id: Option<String>,
+}
+
+impl ServiceError {
+  /// Builds a `ServiceError` from a `{"error":{"code":...,"message":...}}` body.
+  /// Bodies that are not in this shape only keep the status and request id.
+  pub fn from_body(status: StatusCode, request_id: Option<String>, body: &str) -> ServiceError {
+    let json = Json::from_str(body).ok();
+    let error = json.as_ref().and_then(|json| json.find("error"));
+    let field = |name: &str| {
+      error.and_then(|error| error.find(name))
+        .and_then(|value| value.as_string())
+        .map(|value| value.to_string())
+    };
+    ServiceError {
+      status: status,
+      code: field("code"),
+      message: field("message"),
+      inner_error: error.and_then(|error| error.find("innererror")).and_then(InnerError::from_json),
+      request_id: request_id
+    }
+  }
+
+  /// The inner error codes, outermost first.
+  pub fn inner_codes(&self) -> Vec<&str> {
+    let mut codes = Vec::new();
+    let mut inner = self.inner_error.as_ref();
+    while let Some(error) = inner {
+      codes.push(&error.code[..]);
+      inner = error.inner_error.as_ref();
+    }
+    codes
+  }
+}
+
+impl fmt::Display for ServiceError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    try!(write!(f, "{}", self.status));
+    if let Some(ref code) = self.code {
+      try!(write!(f, " {}", code));
+    }
+    if let Some(ref message) = self.message {
+      try!(write!(f, ": {}", message));
+    }
+    for code in self.inner_codes() {
+      try!(write!(f, " <- {}", code));
+    }
+    match self.request_id {
+      Some(ref id) => write!(f, " (x-ms-request-id: {})", id),
+      None => Ok(())
+    }
+  }
+}
+
+/// Errors raised while talking to Azure Key Vault.
+#[derive(Debug)]
+pub enum Error {
+  /// The request could not be sent or the response could not be read.
+  Transport(HttpError),
+  /// Azure Active Directory refused to issue a token, or the vault kept rejecting it;
+  /// the vault's 401 response is kept in the latter case.
+  Authentication(String, Option<ServiceError>),
+  /// A credential is not set up where the code runs, e.g. there is no managed
+  /// identity or `az login`; a `ChainedTokenCredential` moves on to its next source.
+  CredentialUnavailable(String),
+  /// The vault returned 404 for an operation that requires the object to exist.
+  NotFound(ServiceError),
+  /// The caller is authenticated but lacks permission for the operation.
+  Forbidden(ServiceError),
+  /// The vault is throttling requests; `Retry-After` is passed on in seconds when present.
+  Throttled(Option<u64>, ServiceError),
+  /// Any other non-2xx response.
+  Service(ServiceError),
+  /// The response body was not what the operation expected.
+  Decode(DecoderError),
+  /// The request was rejected before it was sent.
+  InvalidArgument(String),
+  /// Reading or writing a local file failed.
+  Io(io::Error),
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      Error::Transport(ref err) => write!(f, "transport error: {}", err),
+      Error::Authentication(ref msg, None) => write!(f, "authentication failed: {}", msg),
+      Error::Authentication(ref msg, Some(ref err)) => write!(f, "authentication failed: {}: {}", msg, err),
+      Error::CredentialUnavailable(ref msg) => write!(f, "credential unavailable: {}", msg),
+      Error::NotFound(ref err) => write!(f, "not found: {}", err),
+      Error::Forbidden(ref err) => write!(f, "forbidden: {}", err),
+      Error::Throttled(Some(secs), ref err) => write!(f, "throttled, retry after {} seconds: {}", secs, err),
+      Error::Throttled(None, ref err) => write!(f, "throttled: {}", err),
+      Error::Service(ref err) => write!(f, "service error: {}", err),
+      Error::Decode(ref err) => write!(f, "decode error: {}", err),
+      Error::InvalidArgument(ref msg) => write!(f, "invalid argument: {}", msg),
+      Error::Io(ref err) => write!(f, "io error: {}", err),
+    }
+  }
+}
+
+impl StdError for Error {
+  fn description(&self) -> &str {
+    match *self {
+      Error::Transport(ref err) => err.description(),
+      Error::Authentication(_, _) => "authentication failed",
+      Error::CredentialUnavailable(_) => "credential unavailable",
+      Error::NotFound(_) => "not found",
+      Error::Forbidden(_) => "forbidden",
+      Error::Throttled(_, _) => "throttled",
+      Error::Service(_) => "service error",
+      Error::Decode(ref err) => err.description(),
+      Error::InvalidArgument(_) => "invalid argument",
+      Error::Io(ref err) => err.description(),
+    }
+  }
+
+  fn cause(&self) -> Option<&StdError> {
+    match *self {
+      Error::Transport(ref err) => Some(err),
+      Error::Decode(ref err) => Some(err),
+      Error::Io(ref err) => Some(err),
+      _ => None
+    }
+  }
+}
+
+impl Error {
+  /// The error Key Vault reported, if the failure came from the service.
+  pub fn service_error(&self) -> Option<&ServiceError> {
+    match *self {
+      Error::Authentication(_, Some(ref err)) |
+      Error::NotFound(ref err) |
+      Error::Forbidden(ref err) |
+      Error::Throttled(_, ref err) |
+      Error::Service(ref err) => Some(err),
+      _ => None
+    }
+  }
+}
+
+impl From<HttpError> for Error {
+  fn from(err: HttpError) -> Error {
+    Error::Transport(err)
+  }
+}
+
+impl From<io::Error> for Error {
+  fn from(err: io::Error) -> Error {
+    Error::Io(err)
+  }
+}
+
+impl From<DecoderError> for Error {
+  fn from(err: DecoderError) -> Error {
+    Error::Decode(err)
+  }
+}
+
+impl From<FromBase64Error> for Error {
+  fn from(err: FromBase64Error) -> Error {
+    Error::Decode(DecoderError::ApplicationError(err.to_string()))
+  }
+}
@@ -1,9 +1,10 @@
 use std::any::Any;
+use std::error::Error as StdError;
 use std::fmt;
 use std::str::{FromStr, from_utf8};
 use std::ops::{Deref, DerefMut};
+use hyper;
 use hyper::header::{Header, HeaderFormat, Scheme};
-use regex::Regex;
 
 /// The `WWW-Authorization` header field.
 #[derive(Clone, PartialEq, Debug)]
@@ -23,29 +24,42 @@ impl<S: Scheme> DerefMut for WwwAuthenticate<S> {
     }
 }
 
+impl<S: Scheme + Any> WwwAuthenticate<S> where <S as FromStr>::Err: 'static {
+    /// Parses the raw header lines. Several lines are read as one comma-separated
+    /// list, and the first challenge for the scheme of `S` is used.
+    pub fn parse(raw: &[Vec<u8>]) -> Result<WwwAuthenticate<S>, ChallengeParseError> {
+        let mut lines = Vec::new();
+        for line in raw {
+            lines.push(try!(from_utf8(&line[..]).map_err(|_| ChallengeParseError::InvalidUtf8)));
+        }
+        let header = lines.join(", ");
+        match <S as Scheme>::scheme() {
+            Some(scheme) => {
+                let challenges = try!(parse_challenges(header.as_ref()));
+                match challenges.iter().find(|challenge| challenge.scheme.eq_ignore_ascii_case(scheme)) {
+                    Some(challenge) => {
+                        let params = challenge.to_string();
+                        params[challenge.scheme.len()..].trim_left().parse::<S>().map(WwwAuthenticate)
+                            .map_err(|_| ChallengeParseError::InvalidChallenge(scheme.to_string()))
+                    },
+                    None => Err(ChallengeParseError::MissingScheme(scheme.to_string()))
+                }
+            },
+            None => header.parse::<S>().map(WwwAuthenticate).map_err(|_| ChallengeParseError::InvalidChallenge(header.clone()))
+        }
+    }
+}
+
 impl<S: Scheme + Any> Header for WwwAuthenticate<S> where <S as FromStr>::Err: 'static{
     fn header_name() -> &'static str {
         "WWW-Authenticate"
     }
 
-    fn parse_header(raw: &[Vec<u8>]) -> Option<WwwAuthenticate<S>> {
-        if raw.len() == 1 {
-            match (from_utf8(unsafe { &raw.get_unchecked(0)[..] }), <S as Scheme>::scheme()) {
-                (Ok(header), Some(scheme))
-                    if header.starts_with(scheme) && header.len() > scheme.len() + 1 => {
-                    header[scheme.len() + 1..].parse::<S>().map(WwwAuthenticate).ok()
-                },
-                (Ok(header), None) => {
-                  header.parse::<S>().map(WwwAuthenticate).ok()
-                },
-                a => {
-                  println!("{:?}", a);
-                  None
-                }
-            }
-        } else {
-            None
-        }
+    fn parse_header(raw: &[Vec<u8>]) -> hyper::Result<WwwAuthenticate<S>> {
+        WwwAuthenticate::parse(raw).map_err(|err| {
+            debug!("WwwAuthenticate::parse_header failed: {}", err);
+            hyper::Error::Header
+        })
     }
 }
 
@@ -84,13 +98,34 @@ impl FromStr for BearerToken {
   }
 }
 
-/// Credential holder for Bearer Authentication
-#[derive(Clone, PartialEq, Debug)]
+/// The `Bearer` challenge Key Vault sends with a 401. Older API versions name the
+/// authority `authorization` and send a `resource`; newer ones send
+/// `authorization_uri` and `scope`, and a claims challenge adds `error`,
+/// `error_description` and base64 `claims`.
+#[derive(Clone, PartialEq, Debug, Default)]
 pub struct Bearer {
-    /// The url to authenticate against
-    pub authorization: String,
+    /// The url to authenticate against, from `authorization` or `authorization_uri`
+    pub authorization: Option<String>,
     /// The resource to authenticate on behalf
-    pub resource: String
+    pub resource: Option<String>,
+    pub scope: Option<String>,
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+    pub claims: Option<String>,
+    /// Any other parameters, such as `realm`, in the order they were sent
+    pub params: Vec<(String, String)>,
+}
+
+impl Bearer {
+    /// The scope to request a token for: `scope` when the challenge names one,
+    /// otherwise `resource` with `/.default` appended.
+    pub fn token_scope(&self) -> Option<String> {
+        match (self.scope.as_ref(), self.resource.as_ref()) {
+            (Some(scope), _) => Some(scope.clone()),
+            (None, Some(resource)) => Some(format!("{}/.default", resource.trim_right_matches('/'))),
+            (None, None) => None
+        }
+    }
 }
 
 impl Scheme for Bearer {
@@ -99,53 +134,280 @@ impl Scheme for Bearer {
     }
 
     fn fmt_scheme(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut text = format!("authorization=\"");
-        text.push_str(self.authorization.as_ref());
-        text.push_str("\", resource=\"");
-        text.push_str(self.resource.as_ref());
-        text.push('"');
-        write!(f, "{}", text)
+        let named = [("authorization", &self.authorization), ("resource", &self.resource), ("scope", &self.scope),
+                     ("error", &self.error), ("error_description", &self.error_description), ("claims", &self.claims)];
+        let params = named.iter()
+            .filter_map(|&(name, value)| value.as_ref().map(|value| (name, &value[..])))
+            .chain(self.params.iter().map(|&(ref name, ref value)| (&name[..], &value[..])));
+        for (index, (name, value)) in params.enumerate() {
+            if index > 0 {
+                try!(write!(f, ", "));
+            }
+            try!(write!(f, "{}={}", name, quote(value)));
+        }
+        Ok(())
     }
 }
 
 impl FromStr for Bearer {
-    type Err = ();
-    fn from_str(s: &str) -> Result<Bearer, ()> {
-      match Regex::new("authorization=\"(.+?)\""){
-        Ok(auth_re) => {
-          match auth_re.captures(s) {
-            Some(auth_cap) => {
-              match Regex::new("resource=\"(.+?)\"") {
-                Ok(resource_re) => {
-                  match resource_re.captures(s) {
-                    Some(re_cap) => {
-                      Ok(Bearer {
-                        authorization: auth_cap.at(1).unwrap().to_string(),
-                        resource: re_cap.at(1).unwrap().to_string()
-                        })
-                    },
-                    None => {
-                      debug!("Bearer::no_resource_capture");
-                      Err(())
+    type Err = ChallengeParseError;
+    fn from_str(s: &str) -> Result<Bearer, ChallengeParseError> {
+        let challenges = try!(parse_challenges(&format!("Bearer {}", s)));
+        let mut bearer = Bearer::default();
+        for (name, value) in challenges.into_iter().next().map(|challenge| challenge.params).unwrap_or(Vec::new()) {
+            match name.as_ref() {
+                "authorization" | "authorization_uri" => bearer.authorization = Some(value),
+                "resource" => bearer.resource = Some(value),
+                "scope" => bearer.scope = Some(value),
+                "error" => bearer.error = Some(value),
+                "error_description" => bearer.error_description = Some(value),
+                "claims" => bearer.claims = Some(value),
+                _ => bearer.params.push((name, value))
+            }
+        }
+        Ok(bearer)
+    }
+}
+
+/// Why a `WWW-Authenticate` header could not be parsed.
+#[derive(Clone, PartialEq, Debug)]
+pub enum ChallengeParseError {
+    /// The header holds no challenges.
+    Empty,
+    /// The header is not valid UTF-8.
+    InvalidUtf8,
+    /// Something else was found at the given byte offset.
+    Expected(&'static str, usize),
+    /// A quoted string starting at the given byte offset is never closed.
+    UnterminatedQuotedString(usize),
+    /// None of the challenges use the expected scheme.
+    MissingScheme(String),
+    /// The challenge for a scheme was rejected by its parser.
+    InvalidChallenge(String),
+}
+
+impl fmt::Display for ChallengeParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ChallengeParseError::Empty => write!(f, "no challenges"),
+            ChallengeParseError::InvalidUtf8 => write!(f, "header is not valid UTF-8"),
+            ChallengeParseError::Expected(what, at) => write!(f, "expected {} at offset {}", what, at),
+            ChallengeParseError::UnterminatedQuotedString(at) => write!(f, "unterminated quoted string at offset {}", at),
+            ChallengeParseError::MissingScheme(ref scheme) => write!(f, "no {} challenge", scheme),
+            ChallengeParseError::InvalidChallenge(ref challenge) => write!(f, "invalid challenge: {}", challenge),
+        }
+    }
+}
+
+impl StdError for ChallengeParseError {
+    fn description(&self) -> &str {
+        "invalid WWW-Authenticate header"
+    }
+}
+
+/// One challenge: an auth scheme with either a token68 or auth-params. Parameter
+/// names are lower-cased, as they are case-insensitive.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Challenge {
+    pub scheme: String,
+    pub token68: Option<String>,
+    pub params: Vec<(String, String)>,
+}
+
+impl Challenge {
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.iter()
+            .find(|&&(ref param, _)| param.eq_ignore_ascii_case(name))
+            .map(|&(_, ref value)| &value[..])
+    }
+}
+
+impl fmt::Display for Challenge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "{}", self.scheme));
+        if let Some(ref token68) = self.token68 {
+            try!(write!(f, " {}", token68));
+        }
+        for (index, &(ref name, ref value)) in self.params.iter().enumerate() {
+            try!(write!(f, "{}{}={}", if index == 0 { " " } else { ", " }, name, quote(value)));
+        }
+        Ok(())
+    }
+}
+
+/// Parses a `WWW-Authenticate` value into its challenges, following RFC 7235:
+/// challenges and their parameters share one comma-separated list, and
+/// parameter values are tokens or quoted strings with backslash escapes.
+pub fn parse_challenges(header: &str) -> Result<Vec<Challenge>, ChallengeParseError> {
+    let mut tokenizer = Tokenizer { input: header, pos: 0 };
+    let mut challenges = Vec::new();
+    loop {
+        tokenizer.skip_separators();
+        if tokenizer.at_end() {
+            break;
+        }
+        let scheme = try!(tokenizer.expect_token("auth scheme"));
+        let mut challenge = Challenge { scheme: scheme.to_string(), token68: None, params: Vec::new() };
+        tokenizer.skip_whitespace();
+
+        if tokenizer.at_param() {
+            loop {
+                let name = try!(tokenizer.expect_token("parameter name"));
+                tokenizer.skip_whitespace();
+                try!(tokenizer.expect_byte(b'=', "'='"));
+                tokenizer.skip_whitespace();
+                let value = if tokenizer.peek() == Some(b'"') {
+                    try!(tokenizer.quoted_string())
+                } else {
+                    try!(tokenizer.expect_token("parameter value")).to_string()
+                };
+                challenge.params.push((name.to_lowercase(), value));
+                tokenizer.skip_whitespace();
+                // after a comma comes either another parameter or the next challenge
+                let before_separator = tokenizer.pos;
+                tokenizer.skip_separators();
+                if !tokenizer.at_param() {
+                    tokenizer.pos = before_separator;
+                    break;
+                }
+                if tokenizer.input[before_separator..tokenizer.pos].find(',').is_none() {
+                    return Err(ChallengeParseError::Expected("','", before_separator));
+                }
+            }
+        } else if let Some(token68) = tokenizer.token68() {
+            challenge.token68 = Some(token68.to_string());
+        }
+
+        tokenizer.skip_whitespace();
+        match tokenizer.peek() {
+            None | Some(b',') => challenges.push(challenge),
+            Some(_) => return Err(ChallengeParseError::Expected("','", tokenizer.pos))
+        }
+    }
+    if challenges.is_empty() {
+        Err(ChallengeParseError::Empty)
+    } else {
+        Ok(challenges)
+    }
+}
+
+// a value as a quoted string, escaping quotes and backslashes
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn is_tchar(byte: u8) -> bool {
+    match byte {
+        b'a'...b'z' | b'A'...b'Z' | b'0'...b'9' => true,
+        b'!' | b'#' | b'$' | b'%' | b'&' | b'\'' | b'*' | b'+' | b'-' | b'.' | b'^' | b'_' | b'`' | b'|' | b'~' => true,
+        _ => false
+    }
+}
+
+fn is_token68_char(byte: u8) -> bool {
+    match byte {
+        b'a'...b'z' | b'A'...b'Z' | b'0'...b'9' | b'-' | b'.' | b'_' | b'~' | b'+' | b'/' => true,
+        _ => false
+    }
+}
+
+struct Tokenizer<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.pos).cloned()
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.input.len()
+    }
+
+    fn skip_while<F: Fn(u8) -> bool>(&mut self, pred: F) -> &'a str {
+        let start = self.pos;
+        while self.peek().map(|byte| pred(byte)).unwrap_or(false) {
+            self.pos += 1;
+        }
+        &self.input[start..self.pos]
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.skip_while(|byte| byte == b' ' || byte == b'\t');
+    }
+
+    fn skip_separators(&mut self) {
+        self.skip_while(|byte| byte == b' ' || byte == b'\t' || byte == b',');
+    }
+
+    fn expect_token(&mut self, what: &'static str) -> Result<&'a str, ChallengeParseError> {
+        match self.skip_while(is_tchar) {
+            "" => Err(ChallengeParseError::Expected(what, self.pos)),
+            token => Ok(token)
+        }
+    }
+
+    fn expect_byte(&mut self, expected: u8, what: &'static str) -> Result<(), ChallengeParseError> {
+        if self.peek() == Some(expected) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(ChallengeParseError::Expected(what, self.pos))
+        }
+    }
+
+    // looks ahead for `token BWS "=" BWS value`, as opposed to a token68
+    // such as `abc=` or `abc==`, or the scheme of the next challenge
+    fn at_param(&self) -> bool {
+        let mut ahead = Tokenizer { input: self.input, pos: self.pos };
+        if ahead.skip_while(is_tchar).is_empty() {
+            return false;
+        }
+        ahead.skip_whitespace();
+        if ahead.expect_byte(b'=', "'='").is_err() {
+            return false;
+        }
+        ahead.skip_whitespace();
+        match ahead.peek() {
+            None | Some(b'=') | Some(b',') => false,
+            Some(_) => true
+        }
+    }
+
+    fn token68(&mut self) -> Option<&'a str> {
+        let start = self.pos;
+        if self.skip_while(is_token68_char).is_empty() {
+            return None;
+        }
+        self.skip_while(|byte| byte == b'=');
+        Some(&self.input[start..self.pos])
+    }
+
+    fn quoted_string(&mut self) -> Result<String, ChallengeParseError> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut value = Vec::new();
+        loop {
+            match self.peek() {
+                Some(b'"') => {
+                    self.pos += 1;
+                    // only ASCII backslashes were removed, so this is still UTF-8
+                    return Ok(String::from_utf8(value).unwrap());
+                },
+                Some(b'\\') => {
+                    match self.input.as_bytes().get(self.pos + 1) {
+                        Some(&escaped) => value.push(escaped),
+                        None => return Err(ChallengeParseError::UnterminatedQuotedString(start))
                     }
-                  }
+                    self.pos += 2;
                 },
-                Err(e) => {
-                  debug!("Bearer::resource_regex_failed error={:?}", e);
-                  Err(())
-                }
-              }
-            },
-            None => {
-              debug!("Bearer::no_auth_capture");
-              Err(())
+                Some(byte) => {
+                    value.push(byte);
+                    self.pos += 1;
+                },
+                None => return Err(ChallengeParseError::UnterminatedQuotedString(start))
             }
-          }
-        },
-        Err(e) => {
-          debug!("Bearer::auth_regex_failed error={:?}", e);
-          Err(())
         }
-      }
     }
 }
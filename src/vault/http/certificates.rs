@@ -0,0 +1,321 @@
+use std::collections::BTreeMap;
+
+use rustc_serialize::base64::{ToBase64, STANDARD};
+
+use http::client::Attributes;
+use macros::JsonField;
+
+json_struct! {
+  #[derive(Debug, Clone, PartialEq, Default)]
+  pub struct KeyProperties {
+    pub exportable: Option<bool> = "exportable",
+    pub kty: Option<String> = "kty",
+    pub key_size: Option<u32> = "key_size",
+    pub reuse_key: Option<bool> = "reuse_key",
+    pub crv: Option<String> = "crv",
+  }
+}
+
+json_struct! {
+  #[derive(Debug, Clone, PartialEq, Default)]
+  pub struct SecretContentProperties {
+    // application/x-pkcs12 or application/x-pem-file
+    pub content_type: Option<String> = "contentType",
+  }
+}
+
+json_struct! {
+  #[derive(Debug, Clone, PartialEq, Default)]
+  pub struct SubjectAlternativeNames {
+    pub emails: Option<Vec<String>> = "emails",
+    pub dns_names: Option<Vec<String>> = "dns_names",
+    pub upns: Option<Vec<String>> = "upns",
+  }
+}
+
+json_struct! {
+  #[derive(Debug, Clone, PartialEq, Default)]
+  pub struct X509Properties {
+    pub subject: Option<String> = "subject",
+    pub sans: Option<SubjectAlternativeNames> = "sans",
+    pub ekus: Option<Vec<String>> = "ekus",
+    pub key_usage: Option<Vec<String>> = "key_usage",
+    pub validity_months: Option<u32> = "validity_months",
+  }
+}
+
+json_struct! {
+  #[derive(Debug, Clone, PartialEq, Default)]
+  pub struct Trigger {
+    pub lifetime_percentage: Option<u32> = "lifetime_percentage",
+    pub days_before_expiry: Option<u32> = "days_before_expiry",
+  }
+}
+
+impl JsonField for Trigger {}
+
+json_struct! {
+  #[derive(Debug, Clone, PartialEq)]
+  pub struct Action {
+    // EmailContacts or AutoRenew
+    pub action_type: String = "action_type",
+  }
+}
+
+impl JsonField for Action {}
+
+json_struct! {
+  #[derive(Debug, Clone, PartialEq)]
+  pub struct LifetimeAction {
+    pub trigger: Trigger = "trigger",
+    pub action: Action = "action",
+  }
+}
+
+json_struct! {
+  #[derive(Debug, Clone, PartialEq, Default)]
+  pub struct IssuerParameters {
+    // Self, Unknown, or the name of an issuer configured in the vault
+    pub name: Option<String> = "name",
+    // certificate type requested from the issuer, e.g. OV-SSL
+    pub cty: Option<String> = "cty",
+    pub cert_transparency: Option<bool> = "cert_transparency",
+  }
+}
+
+json_struct! {
+  /// How Key Vault creates and renews a certificate.
+  #[derive(Debug, Clone, PartialEq, Default)]
+  pub struct CertificatePolicy {
+    pub id: Option<String> = "id",
+    pub key_props: Option<KeyProperties> = "key_props",
+    pub secret_props: Option<SecretContentProperties> = "secret_props",
+    pub x509_props: Option<X509Properties> = "x509_props",
+    pub lifetime_actions: Option<Vec<LifetimeAction>> = "lifetime_actions",
+    pub issuer: Option<IssuerParameters> = "issuer",
+    pub attributes: Option<Attributes> = "attributes",
+  }
+}
+
+impl JsonField for CertificatePolicy {}
+
+impl CertificatePolicy {
+  /// A policy for a self-signed certificate with the given subject, e.g. `CN=example.com`.
+  pub fn self_signed(subject: &str) -> CertificatePolicy {
+    CertificatePolicy::issued_by("Self", subject)
+  }
+
+  /// A policy for a certificate signed by a named issuer, or by `Unknown` when the
+  /// CSR is to be signed outside Key Vault and merged back with `merge_certificate`.
+  pub fn issued_by(issuer: &str, subject: &str) -> CertificatePolicy {
+    CertificatePolicy {
+      key_props: Some(KeyProperties {
+        exportable: Some(true),
+        kty: Some("RSA".to_string()),
+        key_size: Some(2048),
+        reuse_key: Some(false),
+        crv: None
+      }),
+      secret_props: Some(SecretContentProperties { content_type: Some("application/x-pkcs12".to_string()) }),
+      x509_props: Some(X509Properties {
+        subject: Some(subject.to_string()),
+        validity_months: Some(12),
+        ..Default::default()
+      }),
+      issuer: Some(IssuerParameters { name: Some(issuer.to_string()), ..Default::default() }),
+      ..Default::default()
+    }
+  }
+}
+
+json_struct! {
+  /// A certificate together with its DER encoded public part.
+  #[derive(Debug, Clone, PartialEq)]
+  pub struct CertificateBundle {
+    pub id: String = "id",
+    // identifiers of the key and secret backing the certificate
+    pub kid: Option<String> = "kid",
+    pub sid: Option<String> = "sid",
+    pub x5t: Option<String> = "x5t",
+    pub cer: Option<String> = "cer",
+    pub content_type: Option<String> = "contentType",
+    pub attributes: Attributes = "attributes",
+    pub tags: Option<BTreeMap<String, String>> = "tags",
+    pub policy: Option<CertificatePolicy> = "policy",
+  }
+}
+
+#[derive(RustcEncodable, RustcDecodable, Debug, Clone, PartialEq)]
+pub struct CertificateItem {
+  pub id: String,
+  pub x5t: Option<String>,
+  pub attributes: Attributes,
+  pub tags: Option<BTreeMap<String, String>>,
+}
+
+#[derive(RustcEncodable, RustcDecodable, Debug, Clone, PartialEq)]
+pub struct OperationError {
+  pub code: Option<String>,
+  pub message: Option<String>,
+}
+
+/// The pending creation of a certificate.
+#[derive(RustcEncodable, RustcDecodable, Debug, Clone, PartialEq)]
+pub struct CertificateOperation {
+  pub id: Option<String>,
+  pub issuer: Option<IssuerParameters>,
+  // base64 PKCS#10 request, for issuers outside Key Vault
+  pub csr: Option<String>,
+  pub cancellation_requested: Option<bool>,
+  // inProgress, completed, cancelled or failed
+  pub status: Option<String>,
+  pub status_details: Option<String>,
+  pub error: Option<OperationError>,
+  pub target: Option<String>,
+  pub request_id: Option<String>,
+}
+
+impl CertificateOperation {
+  pub fn is_in_progress(&self) -> bool {
+    self.status.as_ref().map(|status| &status[..] == "inProgress").unwrap_or(false)
+  }
+}
+
+json_struct! {
+  /// A certificate to import, with its private key.
+  #[derive(Debug, Clone, PartialEq)]
+  pub struct CertificateImport {
+    // base64 PFX, or the text of a PEM bundle
+    pub value: String = "value",
+    pub pwd: Option<String> = "pwd",
+    pub policy: Option<CertificatePolicy> = "policy",
+    pub attributes: Option<Attributes> = "attributes",
+    pub tags: Option<BTreeMap<String, String>> = "tags",
+  }
+}
+
+impl CertificateImport {
+  pub fn from_pfx(pfx: &[u8], password: Option<&str>) -> CertificateImport {
+    CertificateImport::new(pfx.to_base64(STANDARD), password, "application/x-pkcs12")
+  }
+
+  /// A PEM bundle holding the certificate chain and an unencrypted private key.
+  pub fn from_pem(pem: &str) -> CertificateImport {
+    CertificateImport::new(pem.to_string(), None, "application/x-pem-file")
+  }
+
+  fn new(value: String, password: Option<&str>, content_type: &str) -> CertificateImport {
+    CertificateImport {
+      value: value,
+      pwd: password.map(|password| password.to_string()),
+      policy: Some(CertificatePolicy {
+        secret_props: Some(SecretContentProperties { content_type: Some(content_type.to_string()) }),
+        ..Default::default()
+      }),
+      attributes: None,
+      tags: None
+    }
+  }
+}
+
+json_struct! {
+  #[derive(Debug, Clone, PartialEq, Default)]
+  pub struct IssuerCredentials {
+    pub account_id: Option<String> = "account_id",
+    // never returned by the service
+    pub pwd: Option<String> = "pwd",
+  }
+}
+
+json_struct! {
+  #[derive(Debug, Clone, PartialEq, Default)]
+  pub struct AdministratorDetails {
+    pub first_name: Option<String> = "first_name",
+    pub last_name: Option<String> = "last_name",
+    pub email: Option<String> = "email",
+    pub phone: Option<String> = "phone",
+  }
+}
+
+json_struct! {
+  #[derive(Debug, Clone, PartialEq, Default)]
+  pub struct OrganizationDetails {
+    pub id: Option<String> = "id",
+    pub admin_details: Option<Vec<AdministratorDetails>> = "admin_details",
+  }
+}
+
+json_struct! {
+  /// A certificate issuer configured in the vault.
+  #[derive(Debug, Clone, PartialEq, Default)]
+  pub struct IssuerBundle {
+    pub id: Option<String> = "id",
+    // DigiCert, GlobalSign, or a provider name for issuers handled outside Key Vault
+    pub provider: Option<String> = "provider",
+    pub credentials: Option<IssuerCredentials> = "credentials",
+    pub org_details: Option<OrganizationDetails> = "org_details",
+    pub attributes: Option<Attributes> = "attributes",
+  }
+}
+
+impl IssuerBundle {
+  pub fn new(provider: &str) -> IssuerBundle {
+    IssuerBundle {
+      provider: Some(provider.to_string()),
+      attributes: Some(Attributes { enabled: Some(true), ..Default::default() }),
+      ..Default::default()
+    }
+  }
+
+  pub fn digicert(account_id: &str, api_key: &str, organization_id: &str) -> IssuerBundle {
+    IssuerBundle::new("DigiCert")
+      .with_credentials(account_id, api_key)
+      .with_organization(organization_id, Vec::new())
+  }
+
+  pub fn globalsign(account_id: &str, password: &str, admin_details: Vec<AdministratorDetails>) -> IssuerBundle {
+    IssuerBundle::new("GlobalSign")
+      .with_credentials(account_id, password)
+      .with_organization("", admin_details)
+  }
+
+  pub fn with_credentials(mut self, account_id: &str, password: &str) -> IssuerBundle {
+    self.credentials = Some(IssuerCredentials {
+      account_id: Some(account_id.to_string()),
+      pwd: Some(password.to_string())
+    });
+    self
+  }
+
+  pub fn with_organization(mut self, organization_id: &str, admin_details: Vec<AdministratorDetails>) -> IssuerBundle {
+    self.org_details = Some(OrganizationDetails {
+      id: if organization_id.is_empty() { None } else { Some(organization_id.to_string()) },
+      admin_details: if admin_details.is_empty() { None } else { Some(admin_details) }
+    });
+    self
+  }
+}
+
+#[derive(RustcEncodable, RustcDecodable, Debug, Clone, PartialEq)]
+pub struct IssuerItem {
+  pub id: String,
+  pub provider: Option<String>,
+}
+
+json_struct! {
+  #[derive(Debug, Clone, PartialEq, Default)]
+  pub struct Contact {
+    pub email: Option<String> = "email",
+    pub name: Option<String> = "name",
+    pub phone: Option<String> = "phone",
+  }
+}
+
+json_struct! {
+  /// The people notified about certificate lifetime events.
+  #[derive(Debug, Clone, PartialEq)]
+  pub struct Contacts {
+    pub id: Option<String> = "id",
+    pub contacts: Vec<Contact> = "contacts",
+  }
+}
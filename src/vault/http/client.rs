@@ -3,163 +3,470 @@
 
 use hyper;
 use hyper::client::{Client, Response};
-use hyper::header::{Authorization, ContentType};
-use hyper::HttpError;
+use hyper::header::{Authorization, ContentType, Headers};
 use hyper::method::Method;
-use hyper::method::Method::{Post};
 use hyper::mime::*;
 use hyper::status::{StatusCode, StatusClass};
 
-use url;
-
 use std::collections::BTreeMap;
 use std::cmp::PartialEq;
-use std::error::Error;
-use std::io::Error as IoError;
-use std::io::ErrorKind;
-use std::io::{Read};
+use std::fmt;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::str::from_utf8;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 use std::string::String;
 
 use rustc_serialize::json;
-use rustc_serialize::json::DecodeResult;
-use rustc_serialize::base64::{ToBase64, FromBase64, URL_SAFE};
-use rustc_serialize::Decodable;
+use rustc_serialize::base64::{ToBase64, FromBase64, URL_SAFE, STANDARD};
+use rustc_serialize::{Decodable, Decoder, Encodable, Encoder};
+use rustc_serialize::json::Json;
 
+use auth::{AccessToken, TokenCredential};
+use auth::cache::{CacheKey, TokenCache};
+use cloud::CloudProfile;
+use error::{Error, ServiceError, VaultResult};
+use macros::JsonField;
+use jwk::{JsonWebKey, JsonWebKeyType, JsonWebKeyCurveName, JsonWebKeyEncryptionAlgorithm, JsonWebKeySignatureAlgorithm};
+use http::https_client;
 use http::authenticate_header::*;
+use http::paging::{Paged, PageSource};
+use http::secrets::{DeletedSecretBundle, DeletedSecretItem, SecretBundle, SecretItem, SecretProperties};
+use http::certificates::{CertificateBundle, CertificateImport, CertificateItem, CertificateOperation, CertificatePolicy};
+use http::certificates::{Contact, Contacts, IssuerBundle, IssuerItem};
 
-// authentication bearer token
-#[derive(RustcEncodable, RustcDecodable, Debug, Clone)]
-struct AuthToken {
-  token_type: String,
-  expires_in: i32,
-  expires_on: i32,
-  not_before: i32,
-  resource: String,
-  access_token: String,
-}
-
-// Azure Key Vault asymmetric key representation
+// Azure Key Vault asymmetric key representation, RSA or elliptic curve
 #[derive(RustcEncodable, RustcDecodable, Debug, Clone, PartialEq)]
 pub struct KeyWrapper {
   pub key: Key,
   pub attributes: Attributes,
+  pub tags: Option<BTreeMap<String, String>>,
 }
 
 #[derive(RustcEncodable, RustcDecodable, Debug, Clone, PartialEq)]
 pub struct Key {
   pub kid: String,
   pub kty: String,
-  pub n: String,
-  pub e: String,
   pub key_ops: Vec<String>,
+  // RSA modulus and exponent
+  pub n: Option<String>,
+  pub e: Option<String>,
+  // EC curve and coordinates
+  pub crv: Option<String>,
+  pub x: Option<String>,
+  pub y: Option<String>,
+}
+
+impl JsonField for Key {}
+
+// a key in a soft-delete enabled vault that has been deleted but not purged
+json_struct! {
+  #[derive(Debug, Clone, PartialEq)]
+  pub struct DeletedKeyBundle {
+    pub key: Key = "key",
+    pub attributes: Attributes = "attributes",
+    pub tags: Option<BTreeMap<String, String>> = "tags",
+    pub managed: Option<bool> = "managed",
+    pub recovery_id: Option<String> = "recoveryId",
+    pub deleted_date: Option<i32> = "deletedDate",
+    pub scheduled_purge_date: Option<i32> = "scheduledPurgeDate",
+  }
+}
+
+json_struct! {
+  #[derive(Debug, Clone, PartialEq)]
+  pub struct DeletedKeyItem {
+    pub kid: String = "kid",
+    pub attributes: Attributes = "attributes",
+    pub tags: Option<BTreeMap<String, String>> = "tags",
+    pub managed: Option<bool> = "managed",
+    pub recovery_id: Option<String> = "recoveryId",
+    pub deleted_date: Option<i32> = "deletedDate",
+    pub scheduled_purge_date: Option<i32> = "scheduledPurgeDate",
+  }
 }
 
 #[derive(RustcEncodable, RustcDecodable, Debug, Clone, PartialEq)]
 pub struct KeyListItem {
   pub kid: String,
+  pub attributes: Attributes,
+  pub tags: Option<BTreeMap<String, String>>,
+  pub managed: Option<bool>
+}
+
+// body of a PUT to /secrets/{name}
+json_struct! {
+  #[derive(Debug, Clone, PartialEq)]
+  struct SetSecret {
+    value: String = "value",
+    content_type: Option<String> = "contentType",
+    attributes: Option<Attributes> = "attributes",
+    tags: Option<BTreeMap<String, String>> = "tags",
+  }
+}
+
+// body of a POST to /certificates/{name}/create
+json_struct! {
+  #[derive(Debug, Clone, PartialEq)]
+  struct CreateCertificate {
+    policy: CertificatePolicy = "policy",
+    attributes: Option<Attributes> = "attributes",
+    tags: Option<BTreeMap<String, String>> = "tags",
+  }
+}
+
+// body of a POST to /certificates/{name}/pending/merge
+json_struct! {
+  #[derive(Debug, Clone, PartialEq)]
+  struct MergeCertificate {
+    x5c: Vec<String> = "x5c",
+    attributes: Option<Attributes> = "attributes",
+    tags: Option<BTreeMap<String, String>> = "tags",
+  }
+}
+
+#[derive(RustcEncodable, Debug, Clone, PartialEq)]
+struct CancelCertificateOperation {
+  cancellation_requested: bool
+}
+
+// body of a PATCH to /keys/{name}/{version}
+json_struct! {
+  #[derive(Debug, Clone, PartialEq)]
+  struct UpdateKey {
+    key_ops: Vec<String> = "key_ops",
+    attributes: Attributes = "attributes",
+    tags: Option<BTreeMap<String, String>> = "tags",
+  }
+}
+
+// body of a PUT to /keys/{name}
+#[derive(RustcEncodable, Debug, Clone, PartialEq)]
+struct ImportKey {
+  key: JsonWebKey,
+  hsm: bool,
   attributes: Attributes
 }
 
+// only EC keys have a curve
+json_struct! {
+  #[derive(Debug, Clone, PartialEq)]
+  struct CreateKey {
+    kty: JsonWebKeyType = "kty",
+    crv: Option<JsonWebKeyCurveName> = "crv",
+    key_ops: Vec<String> = "key_ops",
+    attributes: Attributes = "attributes",
+  }
+}
+
+impl JsonField for JsonWebKeyType {}
+
+// result of an encrypt, decrypt, wrap, unwrap or sign operation
 #[derive(RustcEncodable, RustcDecodable, Debug, Clone, PartialEq)]
-struct CreateKey {
-  kty: String,
-  key_ops: Vec<String>,
-  attributes: Attributes
+struct KeyOperationResult {
+  value: String
+}
+
+#[derive(RustcEncodable, RustcDecodable, Debug, Clone, PartialEq)]
+struct KeyVerifyResult {
+  value: bool
 }
 
-#[derive(RustcEncodable, RustcDecodable, PartialEq, Debug, Clone)]
+#[derive(PartialEq, Debug, Clone, Default)]
 pub struct Attributes {
   pub enabled: Option<bool>,
   pub exp: Option<i32>,
-  pub nbf: Option<i32>
+  pub nbf: Option<i32>,
+  // set by the service and never sent
+  pub created: Option<i32>,
+  pub updated: Option<i32>,
+  pub recovery_level: Option<String>
+}
+
+// only the settable attributes are sent, and unset ones are left out
+impl Encodable for Attributes {
+  fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+    s.emit_struct("Attributes", 0, |s| {
+      let mut index = 0;
+      if let Some(enabled) = self.enabled {
+        try!(s.emit_struct_field("enabled", index, |s| enabled.encode(s)));
+        index += 1;
+      }
+      if let Some(exp) = self.exp {
+        try!(s.emit_struct_field("exp", index, |s| exp.encode(s)));
+        index += 1;
+      }
+      if let Some(nbf) = self.nbf {
+        try!(s.emit_struct_field("nbf", index, |s| nbf.encode(s)));
+      }
+      Ok(())
+    })
+  }
+}
+
+impl JsonField for Attributes {}
+
+impl Decodable for Attributes {
+  fn decode<D: Decoder>(d: &mut D) -> Result<Attributes, D::Error> {
+    d.read_struct("Attributes", 6, |d| {
+      Ok(Attributes {
+        enabled: try!(d.read_struct_field("enabled", 0, Decodable::decode)),
+        exp: try!(d.read_struct_field("exp", 1, Decodable::decode)),
+        nbf: try!(d.read_struct_field("nbf", 2, Decodable::decode)),
+        created: try!(d.read_struct_field("created", 3, Decodable::decode)),
+        updated: try!(d.read_struct_field("updated", 4, Decodable::decode)),
+        recovery_level: try!(d.read_struct_field("recoveryLevel", 5, Decodable::decode))
+      })
+    })
+  }
+}
+
+/// Scope of tokens for Azure Key Vault in the public cloud.
+pub const KEY_VAULT_SCOPE: &'static str = "https://vault.azure.net/.default";
+
+/// The Key Vault REST API version requests use unless the builder picks another.
+pub const DEFAULT_API_VERSION: &'static str = "7.4";
+
+// where a client keeps its tokens, under the tenant and client id of its credential
+#[derive(Clone)]
+struct SharedTokenCache {
+  cache: Arc<TokenCache>,
+  tenant_id: String,
+  client_id: String,
+}
+
+impl SharedTokenCache {
+  fn unshared() -> SharedTokenCache {
+    SharedTokenCache { cache: Arc::new(TokenCache::new()), tenant_id: String::new(), client_id: String::new() }
+  }
+
+  fn key(&self, scope: &str) -> CacheKey {
+    CacheKey { tenant_id: self.tenant_id.clone(), scope: scope.to_string(), client_id: self.client_id.clone() }
+  }
+}
+
+// the same cache, not an equal one
+impl PartialEq for SharedTokenCache {
+  fn eq(&self, other: &SharedTokenCache) -> bool {
+    (&*self.cache as *const TokenCache) == (&*other.cache as *const TokenCache) &&
+      self.tenant_id == other.tenant_id && self.client_id == other.client_id
+  }
+}
+
+impl fmt::Debug for SharedTokenCache {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "SharedTokenCache {{ tenant_id: {:?}, client_id: {:?} }}", self.tenant_id, self.client_id)
+  }
+}
+
+/// Configures an `AzureVault`: a vault named in a cloud, or one at a URL such as
+/// a private endpoint or a local emulator, with the API version to speak.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AzureVaultBuilder {
+  vault_url: String,
+  scope: Option<String>,
+  api_version: String,
+  token_cache: Option<SharedTokenCache>,
+  refresh_skew: Option<i64>,
+}
+
+impl AzureVaultBuilder {
+  /// The vault `name` in `cloud`, e.g. `CloudProfile::china()`.
+  pub fn from_name(name: &str, cloud: &CloudProfile) -> AzureVaultBuilder {
+    AzureVaultBuilder {
+      vault_url: cloud.vault_url(name),
+      scope: Some(cloud.scope.clone()),
+      api_version: DEFAULT_API_VERSION.to_string(),
+      token_cache: None,
+      refresh_skew: None
+    }
+  }
+
+  /// The vault at `url`, e.g. `https://myvault.vault.azure.cn`. Tokens are
+  /// requested for the scope of the cloud the host belongs to; other hosts, such
+  /// as private endpoints and emulators, need `with_scope`.
+  pub fn from_url(url: &str) -> AzureVaultBuilder {
+    AzureVaultBuilder {
+      vault_url: url.trim_right_matches('/').to_string(),
+      scope: None,
+      api_version: DEFAULT_API_VERSION.to_string(),
+      token_cache: None,
+      refresh_skew: None
+    }
+  }
+
+  pub fn with_scope(mut self, scope: &str) -> AzureVaultBuilder {
+    self.scope = Some(scope.to_string());
+    self
+  }
+
+  /// Speaks another API version, e.g. `7.3`.
+  pub fn with_api_version(mut self, api_version: &str) -> AzureVaultBuilder {
+    self.api_version = api_version.to_string();
+    self
+  }
+
+  /// Keeps tokens in `cache`, shared with other clients and credentials, under
+  /// the tenant and client id of the credential passed to `build`.
+  pub fn with_token_cache(mut self, cache: Arc<TokenCache>, tenant_id: &str, client_id: &str) -> AzureVaultBuilder {
+    self.token_cache = Some(SharedTokenCache { cache: cache, tenant_id: tenant_id.to_string(), client_id: client_id.to_string() });
+    self
+  }
+
+  /// Renews tokens `seconds` before they expire, rather than at the skew of the
+  /// token cache.
+  pub fn with_refresh_skew(mut self, seconds: i64) -> AzureVaultBuilder {
+    self.refresh_skew = Some(seconds);
+    self
+  }
+
+  pub fn build(self, credential: Box<TokenCredential>) -> VaultResult<AzureVault> {
+    let host = {
+      let rest = match (self.vault_url.find("://"), self.vault_url.starts_with("https://") || self.vault_url.starts_with("http://")) {
+        (Some(index), true) => &self.vault_url[index + 3..],
+        _ => return Err(Error::InvalidArgument(format!("vault URL {} is not an http or https URL", self.vault_url)))
+      };
+      if rest.contains('?') || rest.contains('#') {
+        return Err(Error::InvalidArgument(format!("vault URL {} has a query or fragment", self.vault_url)));
+      }
+      rest.split(|c| c == '/' || c == ':').next().unwrap_or("").to_lowercase()
+    };
+    if host.is_empty() {
+      return Err(Error::InvalidArgument(format!("vault URL {} has no host", self.vault_url)));
+    }
+    // a token for a cloud's vaults is not sent to a host outside it unless asked to
+    let scope = match self.scope.or_else(|| CloudProfile::for_host(host.as_ref()).map(|cloud| cloud.scope)) {
+      Some(scope) => scope,
+      None => return Err(Error::InvalidArgument(format!("vault URL {} is not in a known cloud; give its token scope with with_scope", self.vault_url)))
+    };
+    let token_cache = self.token_cache.unwrap_or_else(SharedTokenCache::unshared);
+    Ok(AzureVault {
+      client: try!(https_client()),
+      vault_url: self.vault_url,
+      api_version: self.api_version,
+      credential: credential,
+      scope: scope,
+      refresh_skew: self.refresh_skew.unwrap_or(token_cache.cache.skew()),
+      token_cache: token_cache
+    })
+  }
 }
 
-pub struct AzureVault<'a>{
+pub struct AzureVault {
   client: Client,
-  vault_name: &'a str,
-  key: &'a str,
-  secret: &'a str,
-  auth_token: Option<AuthToken>
-}
-
-impl<'a> AzureVault<'a> {
-
-    fn execute_wrapper<F: Fn(&mut Client, Option<AuthToken>) -> hyper::HttpResult<Response>>(vault_client: &mut AzureVault, req_fn: F) -> hyper::HttpResult<Response>{
-      match req_fn(&mut vault_client.client, vault_client.auth_token.clone()) {
-        Ok(res) => {
-          match res.status {
-            StatusCode::Unauthorized => {
-              match AzureVault::handle_401(vault_client, res) {
-                Ok(mut auth_res) => {
-                    let mut body = String::new();
-                    let _ = auth_res.read_to_string(&mut body);
-                    match auth_res.status.class() {
-                        StatusClass::Success => {
-                            let decode_result: DecodeResult<AuthToken> = json::decode(body.as_ref());
-                            match decode_result {
-                                Ok(token) => {
-                                    vault_client.auth_token = Some(token);
-                                    req_fn(&mut vault_client.client, vault_client.auth_token.clone())
-                                },
-                                Err(err) => {
-                                    Err(HttpError::from(IoError::new(ErrorKind::Other, err.description())))
-                                }
-                            }
-                        },
-                        _ => Err(HttpError::from(IoError::new(ErrorKind::Other, &format!("Status: {} :: Response: {}", auth_res.status, body)[..])))
-                    }
-                },
-                Err(err) => Err(err)
-              }
-            },
-            _ => Ok(res)
-          }
+  // e.g. `https://myvault.vault.azure.net`, without a trailing slash
+  vault_url: String,
+  api_version: String,
+  credential: Box<TokenCredential>,
+  // what tokens are requested for; a challenge naming another resource replaces it
+  scope: String,
+  token_cache: SharedTokenCache,
+  refresh_skew: i64
+}
+
+impl AzureVault {
+
+    fn execute_wrapper<F: Fn(&mut Client, &AccessToken) -> hyper::Result<Response>>(vault_client: &mut AzureVault, req_fn: F) -> VaultResult<Response>{
+      let token = try!(AzureVault::current_token(vault_client));
+      let res = try!(req_fn(&mut vault_client.client, &token));
+      match res.status {
+        StatusCode::Unauthorized => {
+          let token = try!(AzureVault::handle_401(vault_client, res, &token));
+          Ok(try!(req_fn(&mut vault_client.client, &token)))
         },
-        Err(err) => Err(err)
+        _ => Ok(res)
       }
     }
 
-    fn handle_401(vault_client: &mut AzureVault, response: Response) -> hyper::HttpResult<Response>{
+    // the token for the next request, renewed ahead of its expiry rather than after a 401
+    fn current_token(vault_client: &mut AzureVault) -> VaultResult<AccessToken> {
+      let key = vault_client.token_cache.key(&vault_client.scope[..]);
+      let credential = &vault_client.credential;
+      let scopes = [&vault_client.scope[..]];
+      vault_client.token_cache.cache.get_or_refresh_within(key, vault_client.refresh_skew, || credential.get_token(&scopes))
+    }
+
+    // the token was rejected: ask for a new one, for the resource the challenge
+    // names and satisfying the claims of a Continuous Access Evaluation challenge
+    fn handle_401(vault_client: &mut AzureVault, response: Response, rejected: &AccessToken) -> VaultResult<AccessToken>{
       let bearer_header = response.headers.get::<WwwAuthenticate<Bearer>>();
       match bearer_header {
         Some(header) => {
-          let mut auth_url = header.0.authorization.clone();
-          auth_url.push_str("/oauth2/token");
-          let resource = header.0.resource.as_ref();
-          AzureVault::authenticate(&mut vault_client.client, auth_url.as_ref(), resource, vault_client.key, vault_client.secret)
+          if let Some(scope) = header.0.token_scope() {
+            if scope != vault_client.scope {
+              try!(verify_challenge_scope(&vault_client.vault_url[..], &scope[..]));
+              debug!("vault asked for a token to {} rather than {}", scope, vault_client.scope);
+              vault_client.scope = scope;
+            }
+          }
+          let key = vault_client.token_cache.key(&vault_client.scope[..]);
+          let cache = &vault_client.token_cache.cache;
+          let skew = vault_client.refresh_skew;
+          let credential = &vault_client.credential;
+          let scopes = [&vault_client.scope[..]];
+          match (header.0.error.as_ref().map(|error| &error[..]), header.0.claims.as_ref()) {
+            (Some("insufficient_claims"), Some(claims)) => {
+              let claims = try!(decode_claims(claims));
+              debug!("vault asked for a token with claims {}", claims);
+              // a cached token may lack the claims whether or not another client replaced it
+              cache.refresh(key, None, skew, || credential.get_token_with_claims(&scopes, claims.as_ref()))
+            },
+            _ => cache.refresh(key, Some(rejected), skew, || credential.refresh_token(&scopes))
+          }
         },
-        None => panic!("401 with no WWW-Authenticate header!")
+        None => Err(Error::Authentication(format!("401 with no WWW-Authenticate header"), None))
       }
     }
 
-    fn authenticate(client: &mut Client, auth_url: &str, resource: &str, key: &str, secret: &str) -> hyper::HttpResult<Response> {
-      let parmas = vec![("client_id", key),
-                        ("client_secret", secret),
-                        ("resource", resource),
-                        ("grant_type", "client_credentials")];
-      let headers = vec![("content", "application/x-www-form-urlencoded")];
-      AzureVault::pstar_with_params(client, Method::Post, auth_url, parmas.into_iter(), headers.into_iter())
+    fn send(vault_client: &mut AzureVault, method: Method, url: &str) -> VaultResult<Response> {
+      let execute_request = |client: &mut Client, token: &AccessToken| {
+        let mut req_headers = hyper::header::Headers::new();
+        req_headers.set(Authorization(BearerToken { token: token.token.clone() }));
+        client.request(method.clone(), url).headers(req_headers).send()
+      };
+      AzureVault::execute_wrapper(vault_client, execute_request)
     }
 
-    fn pstar_with_params<I, J>(client: &mut Client, method: Method, url: &str, params: I, headers: J) -> Result<Response, HttpError>
-                          where I: Iterator<Item = (&'a str, &'a str)>,
-                                J: Iterator<Item = (&'static str, &'a str)>{
-      let mut req_headers = hyper::header::Headers::new();
-      let post_body = url::form_urlencoded::serialize(params);
+    fn send_json(vault_client: &mut AzureVault, method: Method, url: &str, request_body: &str) -> VaultResult<Response> {
+      let execute_request = |client: &mut Client, token: &AccessToken| {
+        let mut req_headers = hyper::header::Headers::new();
+        let json_mime: Mime = "application/json".parse().unwrap();
+        req_headers.set(Authorization(BearerToken { token: token.token.clone() }));
+        req_headers.set(ContentType(json_mime));
+        client.request(method.clone(), url).headers(req_headers).body(request_body).send()
+      };
+      AzureVault::execute_wrapper(vault_client, execute_request)
+    }
 
-      for (key, value) in headers {
-        let static_key: &'static str = key.clone();
-        req_headers.set_raw(static_key, vec![value.as_bytes().to_vec()])
+    // Key Vault signs digests, so catch a hash that doesn't match the algorithm before sending it
+    fn check_digest(alg: JsonWebKeySignatureAlgorithm, digest: &[u8]) -> VaultResult<()> {
+      if digest.len() == alg.digest_len() {
+        Ok(())
+      } else {
+        Err(Error::InvalidArgument(format!("{} expects a {} byte digest, got {} bytes", alg, alg.digest_len(), digest.len())))
       }
+    }
 
-      let hyper_body: &str = post_body.as_ref();
-      client.request(method, url)
-        .headers(req_headers)
-        .body(hyper_body)
-        .send()
+    fn split_kid(kid: &str) -> Option<(&str, Option<&str>)> {
+      let path = match kid.find("/keys/") {
+        Some(index) => kid[index + "/keys/".len()..].split('?').next().unwrap_or(""),
+        None => return None
+      };
+      let mut segments = path.split('/').filter(|segment| !segment.is_empty());
+      match (segments.next(), segments.next()) {
+        (Some(name), version) => Some((name, version)),
+        (None, _) => None
+      }
     }
 
-    fn key_url<'b>(vault_name: &str, key_name: &str, operation: Option<&str>) -> String{
+    fn object_url(&self, collection: &str, name: &str, version: Option<&str>, operation: Option<&str>) -> String{
+      let version_string = match version {
+          Some(version) => format!("/{}", version),
+          None => format!("")
+      };
       let op_string = match operation {
           Some(op) => {
             format!("/{}", op)
@@ -168,260 +475,669 @@ impl<'a> AzureVault<'a> {
             format!{""}
           }
       };
-      format!("https://{}.vault.azure.net/keys/{}{}?api-version=2014-12-08-preview", vault_name, key_name, op_string)
+      format!("{}/{}/{}{}{}?api-version={}", self.vault_url, collection, name, version_string, op_string, self.api_version)
+    }
+
+    fn key_url(&self, key_name: &str, version: Option<&str>, operation: Option<&str>) -> String{
+      self.object_url("keys", key_name, version, operation)
+    }
+
+    fn secret_url(&self, secret_name: &str, version: Option<&str>, operation: Option<&str>) -> String{
+      self.object_url("secrets", secret_name, version, operation)
+    }
+
+    fn certificate_url(&self, certificate_name: &str, version: Option<&str>, operation: Option<&str>) -> String{
+      self.object_url("certificates", certificate_name, version, operation)
+    }
+
+    fn issuer_url(&self, issuer_name: &str) -> String{
+      self.object_url("certificates/issuers", issuer_name, None, None)
+    }
+
+    fn deleted_key_url(&self, key_name: &str, operation: Option<&str>) -> String{
+      self.object_url("deletedkeys", key_name, None, operation)
+    }
+
+    fn deleted_secret_url(&self, secret_name: &str, operation: Option<&str>) -> String{
+      self.object_url("deletedsecrets", secret_name, None, operation)
+    }
+
+    fn root_url(&self, collection: &str) -> String{
+        format!("{}/{}?api-version={}", self.vault_url, collection, self.api_version)
+    }
+
+    fn with_max_results(url: String, max_results: Option<u32>) -> String {
+        match max_results {
+          Some(max_results) => format!("{}&maxresults={}", url, max_results),
+          None => url
+        }
+    }
+
+    fn handle_response<T>(response: Response, fail_on_404: bool) -> VaultResult<Option<T>> where T : PartialEq + Decodable {
+        match response.status.to_u16() {
+          404 if !fail_on_404 => Ok(None),
+          _ => AzureVault::expect_response(response).map(Some)
+        }
     }
 
-    fn root_keys_url<'b>(vault_name: &str) -> String{
-        format!("https://{}.vault.azure.net/keys?api-version=2014-12-08-preview", vault_name)
+    fn expect_empty(response: Response) -> VaultResult<()> {
+        match response.status.class() {
+          StatusClass::Success => Ok(()),
+          _ => Err(AzureVault::error_for_status(response))
+        }
     }
 
-    fn handle_response<T>(mut response: Response, fail_on_404: bool) -> Option<T> where T : PartialEq + Decodable {
+    fn expect_response<T>(mut response: Response) -> VaultResult<T> where T : PartialEq + Decodable {
         match response.status.to_u16() {
-           100...299 => {
+          100...299 => {
             let mut body = String::new();
             let _ = response.read_to_string(&mut body);
-            let obj_result: DecodeResult<T> = json::decode(body.as_ref());
-            match obj_result {
-                Ok(obj) => Some(obj),
-                Err(err) => panic!(err)
-            }
+            let obj: T = try!(json::decode(body.as_ref()));
+            Ok(obj)
           },
-          404 => match fail_on_404 {
-                    true => panic!("Error:: Status: {}", response.status),
-                    false => None
-                },
-          _ => panic!("Error:: Status: {}", response.status)
+          _ => Err(AzureVault::error_for_status(response))
         }
     }
-}
 
-pub trait Vault<'a>: {
-  fn new(vault_name: &'a str, key: &'a str, secret: &'a str) -> Self;
-  fn list<'b>(&mut self) -> hyper::HttpResult<Option<Vec<KeyListItem>>>;
-  fn get_key<'b>(&mut self, key_name: &str) -> hyper::HttpResult<Option<KeyWrapper>>;
-  fn update_key<'b>(&mut self, key: KeyWrapper) -> hyper::HttpResult<KeyWrapper>;
-  fn delete_key<'b>(&mut self, key_name: &str) -> hyper::HttpResult<Option<KeyWrapper>>;
-  fn create_key<'b>(&mut self, key_name: &str, key_ops: Vec<String>) -> hyper::HttpResult<KeyWrapper>;
-  fn encrypt<'b>(&mut self, key_name: &str, data: &[u8]) -> hyper::HttpResult<String>;
-  fn decrypt<'b>(&mut self, key_name: &str, data: &[u8]) -> hyper::HttpResult<String>;
-  fn wrap<'b>(&mut self, key_name: &str, cek:  &[u8]) -> hyper::HttpResult<String>;
-  fn unwrap<'b>(&mut self, key_name: &str, cek:  &[u8]) -> hyper::HttpResult<String>;
-  fn sign<'b>(&mut self, key_name: &str, message:  Vec<u8>) -> hyper::HttpResult<Vec<u8>>;
-  fn verify<'b>(&mut self, key_name: &str, message:  Vec<u8>, signiture: Vec<u8>) -> hyper::HttpResult<bool>;
-  fn crypto_operation<T>(&mut self, payload: BTreeMap<&str, String>, url: String) -> hyper::HttpResult<T> where T : PartialEq + Decodable;
-}
-
-impl<'a> Vault<'a> for AzureVault<'a> {
-  fn new(vault_name: &'a str, key: &'a str, secret: &'a str) -> AzureVault<'a> {
-    AzureVault{
-      client: Client::new(),
-      vault_name: vault_name,
-      key: key,
-      secret: secret,
-      auth_token: None
+    fn error_for_status(mut response: Response) -> Error {
+        let mut body = String::new();
+        let _ = response.read_to_string(&mut body);
+        let request_id = AzureVault::raw_header(&response.headers, "x-ms-request-id");
+        let service_error = ServiceError::from_body(response.status, request_id, body.as_ref());
+        match response.status {
+          StatusCode::Unauthorized => Error::Authentication(format!("the vault rejected the token"), Some(service_error)),
+          StatusCode::Forbidden => Error::Forbidden(service_error),
+          StatusCode::NotFound => Error::NotFound(service_error),
+          StatusCode::TooManyRequests => {
+            let retry_after = AzureVault::raw_header(&response.headers, "Retry-After")
+              .and_then(|value| value.trim().parse::<u64>().ok());
+            Error::Throttled(retry_after, service_error)
+          },
+          _ => Error::Service(service_error)
+        }
     }
+
+    fn raw_header(headers: &Headers, name: &str) -> Option<String> {
+        headers.get_raw(name)
+          .and_then(|raw| raw.first())
+          .and_then(|value| from_utf8(&value[..]).ok())
+          .map(|value| value.to_string())
+    }
+}
+
+// the host of an http or https URL, lowercased and without the port
+fn url_host(url: &str) -> Option<String> {
+  let rest = match url.find("://") {
+    Some(index) => &url[index + 3..],
+    None => return None
+  };
+  let host = rest.split(|c| c == '/' || c == ':' || c == '?' || c == '#').next().unwrap_or("").to_lowercase();
+  if host.is_empty() { None } else { Some(host) }
+}
+
+// a challenge may only move tokens to a resource whose host the vault's host is
+// part of, or any server answering 401 could collect tokens for any resource
+fn verify_challenge_scope(vault_url: &str, scope: &str) -> VaultResult<()> {
+  match (url_host(vault_url), url_host(scope)) {
+    (Some(ref vault_host), Some(ref resource_host))
+        if vault_host == resource_host || vault_host.ends_with(&format!(".{}", resource_host)[..]) => Ok(()),
+    _ => Err(Error::Authentication(format!("the vault at {} asked for a token to {}, a resource it is not part of", vault_url, scope), None))
   }
+}
 
-  fn get_key<'b>(&mut self, key_name: &str) -> hyper::HttpResult<Option<KeyWrapper>>{
-    let url_str = AzureVault::key_url(self.vault_name, key_name, None);
-    let url: &str = url_str.as_ref();
-    let execute_get_key = |client: &mut Client, auth_token: Option<AuthToken>| {
-      match auth_token {
-        Some(token) => {
-          let mut req_headers = hyper::header::Headers::new();
-          req_headers.set(Authorization(BearerToken { token: token.access_token.clone() }));
-          client.get(url).headers(req_headers).send()
-        },
-        None => client.get(url).send()
-      }
-    };
+// the JSON of a claims challenge, which Key Vault sends base64-encoded
+fn decode_claims(claims: &str) -> VaultResult<String> {
+  claims.from_base64().ok()
+    .and_then(|bytes| String::from_utf8(bytes).ok())
+    .ok_or_else(|| Error::Authentication(format!("claims challenge is not base64 JSON: {}", claims), None))
+}
 
-    match AzureVault::execute_wrapper(self, execute_get_key) {
-        Ok(response) => Ok(AzureVault::handle_response::<KeyWrapper>(response, false)),
-        Err(err) => Err(err)
+fn poll_until<F>(interval: Duration, max_attempts: u32, mut done: F) -> VaultResult<bool> where F: FnMut() -> VaultResult<bool> {
+  for attempt in 0..max_attempts {
+    if try!(done()) {
+      return Ok(true);
+    }
+    if attempt + 1 < max_attempts {
+      thread::sleep(interval);
     }
+  }
+  Ok(false)
+}
 
+impl PageSource for AzureVault {
+  fn fetch_page(&mut self, url: &str) -> VaultResult<Json> {
+    let response = try!(AzureVault::send(self, Method::Get, url));
+    AzureVault::expect_response::<Json>(response)
   }
+}
 
-  fn update_key<'b>(&mut self, key: KeyWrapper) -> hyper::HttpResult<KeyWrapper>{
-    Ok(key)
+pub trait Vault<'a>: {
+  fn new(vault_name: &'a str, credential: Box<TokenCredential>) -> VaultResult<Self> where Self: Sized;
+  fn list<'b>(&'b mut self, max_results: Option<u32>) -> Paged<'b, KeyListItem>;
+  fn get_key_version<'b>(&mut self, key_name: &str, version: Option<&str>) -> VaultResult<Option<KeyWrapper>>;
+  fn list_key_versions<'b>(&'b mut self, key_name: &str, max_results: Option<u32>) -> Paged<'b, KeyListItem>;
+  fn update_key<'b>(&mut self, key: KeyWrapper) -> VaultResult<KeyWrapper>;
+  fn delete_key<'b>(&mut self, key_name: &str) -> VaultResult<Option<DeletedKeyBundle>>;
+  fn create_key<'b>(&mut self, key_name: &str, kty: JsonWebKeyType, crv: Option<JsonWebKeyCurveName>, key_ops: Vec<String>) -> VaultResult<KeyWrapper>;
+  fn encrypt_version<'b>(&mut self, key_name: &str, version: Option<&str>, alg: JsonWebKeyEncryptionAlgorithm, data: &[u8]) -> VaultResult<Vec<u8>>;
+  fn decrypt_version<'b>(&mut self, key_name: &str, version: Option<&str>, alg: JsonWebKeyEncryptionAlgorithm, data: &[u8]) -> VaultResult<Vec<u8>>;
+  fn wrap_version<'b>(&mut self, key_name: &str, version: Option<&str>, alg: JsonWebKeyEncryptionAlgorithm, cek:  &[u8]) -> VaultResult<Vec<u8>>;
+  fn unwrap_version<'b>(&mut self, key_name: &str, version: Option<&str>, alg: JsonWebKeyEncryptionAlgorithm, wrapped_cek:  &[u8]) -> VaultResult<Vec<u8>>;
+  fn sign_version<'b>(&mut self, key_name: &str, version: Option<&str>, alg: JsonWebKeySignatureAlgorithm, message:  Vec<u8>) -> VaultResult<Vec<u8>>;
+  fn verify_version<'b>(&mut self, key_name: &str, version: Option<&str>, alg: JsonWebKeySignatureAlgorithm, message:  Vec<u8>, signiture: Vec<u8>) -> VaultResult<bool>;
+  fn import_key<'b>(&mut self, key_name: &str, key: JsonWebKey, attributes: Attributes, hsm: bool) -> VaultResult<KeyWrapper>;
+  fn backup_key<'b>(&mut self, key_name: &str) -> VaultResult<Vec<u8>>;
+  fn restore_key<'b>(&mut self, backup: &[u8]) -> VaultResult<KeyWrapper>;
+  fn crypto_operation<T>(&mut self, payload: BTreeMap<&str, String>, url: String) -> VaultResult<T> where T : PartialEq + Decodable;
+  fn set_secret<'b>(&mut self, secret_name: &str, value: &str, properties: SecretProperties) -> VaultResult<SecretBundle>;
+  fn get_secret<'b>(&mut self, secret_name: &str, version: Option<&str>) -> VaultResult<Option<SecretBundle>>;
+  fn list_secrets<'b>(&'b mut self, max_results: Option<u32>) -> Paged<'b, SecretItem>;
+  fn list_secret_versions<'b>(&'b mut self, secret_name: &str, max_results: Option<u32>) -> Paged<'b, SecretItem>;
+  fn delete_secret<'b>(&mut self, secret_name: &str) -> VaultResult<Option<DeletedSecretBundle>>;
+  fn update_secret_properties<'b>(&mut self, secret_name: &str, version: Option<&str>, properties: SecretProperties) -> VaultResult<SecretItem>;
+  fn create_certificate<'b>(&mut self, certificate_name: &str, policy: CertificatePolicy, attributes: Option<Attributes>, tags: Option<BTreeMap<String, String>>) -> VaultResult<CertificateOperation>;
+  fn import_certificate<'b>(&mut self, certificate_name: &str, import: CertificateImport) -> VaultResult<CertificateBundle>;
+  fn get_certificate<'b>(&mut self, certificate_name: &str, version: Option<&str>) -> VaultResult<Option<CertificateBundle>>;
+  fn list_certificates<'b>(&'b mut self, max_results: Option<u32>) -> Paged<'b, CertificateItem>;
+  fn list_certificate_versions<'b>(&'b mut self, certificate_name: &str, max_results: Option<u32>) -> Paged<'b, CertificateItem>;
+  fn get_certificate_policy<'b>(&mut self, certificate_name: &str) -> VaultResult<CertificatePolicy>;
+  fn update_certificate_policy<'b>(&mut self, certificate_name: &str, policy: CertificatePolicy) -> VaultResult<CertificatePolicy>;
+  fn get_certificate_operation<'b>(&mut self, certificate_name: &str) -> VaultResult<Option<CertificateOperation>>;
+  fn cancel_certificate_operation<'b>(&mut self, certificate_name: &str) -> VaultResult<CertificateOperation>;
+  fn delete_certificate_operation<'b>(&mut self, certificate_name: &str) -> VaultResult<Option<CertificateOperation>>;
+  fn merge_certificate<'b>(&mut self, certificate_name: &str, x509_certificates: Vec<Vec<u8>>, attributes: Option<Attributes>, tags: Option<BTreeMap<String, String>>) -> VaultResult<CertificateBundle>;
+  fn set_certificate_issuer<'b>(&mut self, issuer_name: &str, issuer: IssuerBundle) -> VaultResult<IssuerBundle>;
+  fn get_certificate_issuer<'b>(&mut self, issuer_name: &str) -> VaultResult<Option<IssuerBundle>>;
+  fn update_certificate_issuer<'b>(&mut self, issuer_name: &str, issuer: IssuerBundle) -> VaultResult<IssuerBundle>;
+  fn delete_certificate_issuer<'b>(&mut self, issuer_name: &str) -> VaultResult<Option<IssuerBundle>>;
+  fn list_certificate_issuers<'b>(&'b mut self, max_results: Option<u32>) -> Paged<'b, IssuerItem>;
+  fn set_certificate_contacts<'b>(&mut self, contacts: Vec<Contact>) -> VaultResult<Contacts>;
+  fn get_certificate_contacts<'b>(&mut self) -> VaultResult<Option<Contacts>>;
+  fn delete_certificate_contacts<'b>(&mut self) -> VaultResult<Option<Contacts>>;
+  fn list_deleted_keys<'b>(&'b mut self, max_results: Option<u32>) -> Paged<'b, DeletedKeyItem>;
+  fn get_deleted_key<'b>(&mut self, key_name: &str) -> VaultResult<Option<DeletedKeyBundle>>;
+  fn recover_deleted_key<'b>(&mut self, key_name: &str) -> VaultResult<KeyWrapper>;
+  fn purge_deleted_key<'b>(&mut self, key_name: &str) -> VaultResult<()>;
+  fn list_deleted_secrets<'b>(&'b mut self, max_results: Option<u32>) -> Paged<'b, DeletedSecretItem>;
+  fn get_deleted_secret<'b>(&mut self, secret_name: &str) -> VaultResult<Option<DeletedSecretBundle>>;
+  fn recover_deleted_secret<'b>(&mut self, secret_name: &str) -> VaultResult<SecretItem>;
+  fn purge_deleted_secret<'b>(&mut self, secret_name: &str) -> VaultResult<()>;
+
+  // the unversioned operations always use the latest version of the key
+  fn get_key<'b>(&mut self, key_name: &str) -> VaultResult<Option<KeyWrapper>> {
+    self.get_key_version(key_name, None)
   }
 
-  fn delete_key<'b>(&mut self, key_name: &str) -> hyper::HttpResult<Option<KeyWrapper>>{
-    let url_str = AzureVault::key_url(self.vault_name, key_name, None);
-    let url: &str = url_str.as_ref();
-    let execute_delete_key = |client: &mut Client, auth_token: Option<AuthToken>| {
-      match auth_token {
-        Some(token) => {
-          let mut req_headers = hyper::header::Headers::new();
-          req_headers.set(Authorization(BearerToken { token: token.access_token.clone() }));
-          client.delete(url).headers(req_headers).send()
-        },
-        None => client.delete(url).send()
-      }
-    };
+  fn encrypt<'b>(&mut self, key_name: &str, alg: JsonWebKeyEncryptionAlgorithm, data: &[u8]) -> VaultResult<Vec<u8>> {
+    self.encrypt_version(key_name, None, alg, data)
+  }
 
-    match AzureVault::execute_wrapper(self, execute_delete_key) {
-        Ok(response) => Ok(AzureVault::handle_response::<KeyWrapper>(response, false)),
-        Err(err) => Err(err)
-    }
+  fn decrypt<'b>(&mut self, key_name: &str, alg: JsonWebKeyEncryptionAlgorithm, data: &[u8]) -> VaultResult<Vec<u8>> {
+    self.decrypt_version(key_name, None, alg, data)
   }
 
-  fn create_key<'b>(&mut self, key_name: &str, key_ops: Vec<String>) -> hyper::HttpResult<KeyWrapper>{
-    let url_str = AzureVault::key_url(self.vault_name, key_name, Some("create"));
-    let url: &str = url_str.as_ref();
-    let create_key = CreateKey{kty: "RSA".to_string(), key_ops: key_ops, attributes: Attributes{enabled: Some(true), nbf: None, exp: None}};
-    let request_body = json::encode(&create_key).unwrap();
-    let execute_create_key = |client: &mut Client, auth_token: Option<AuthToken>| {
-      match auth_token {
-        Some(token) => {
-          let mut req_headers = hyper::header::Headers::new();
-          let json_mime: Mime = "application/json".parse().unwrap();
-          req_headers.set(Authorization(BearerToken { token: token.access_token.clone() }));
-          req_headers.set(ContentType(json_mime));
-          let body: &str = request_body.as_ref();
-          client.post(url).headers(req_headers).body(body).send()
-        },
-        None => {
-          let mut req_headers = hyper::header::Headers::new();
-          let json_mime: Mime = "application/json".parse().unwrap();
-          req_headers.set(ContentType(json_mime));
-          let body: &str = request_body.as_ref();
-          client.post(url).headers(req_headers).body(body).send()
-        }
+  fn wrap<'b>(&mut self, key_name: &str, alg: JsonWebKeyEncryptionAlgorithm, cek:  &[u8]) -> VaultResult<Vec<u8>> {
+    self.wrap_version(key_name, None, alg, cek)
+  }
+
+  fn unwrap<'b>(&mut self, key_name: &str, alg: JsonWebKeyEncryptionAlgorithm, wrapped_cek:  &[u8]) -> VaultResult<Vec<u8>> {
+    self.unwrap_version(key_name, None, alg, wrapped_cek)
+  }
+
+  fn sign<'b>(&mut self, key_name: &str, alg: JsonWebKeySignatureAlgorithm, message:  Vec<u8>) -> VaultResult<Vec<u8>> {
+    self.sign_version(key_name, None, alg, message)
+  }
+
+  fn verify<'b>(&mut self, key_name: &str, alg: JsonWebKeySignatureAlgorithm, message:  Vec<u8>, signiture: Vec<u8>) -> VaultResult<bool> {
+    self.verify_version(key_name, None, alg, message, signiture)
+  }
+
+  // polls the pending operation until it is no longer in progress, or max_attempts polls were made
+  fn wait_for_certificate_operation<'b>(&mut self, certificate_name: &str, interval: Duration, max_attempts: u32) -> VaultResult<CertificateOperation> {
+    let mut attempts = 0;
+    loop {
+      let operation = match try!(self.get_certificate_operation(certificate_name)) {
+        Some(operation) => operation,
+        None => return Err(Error::InvalidArgument(format!("no pending operation for certificate {}", certificate_name)))
+      };
+      attempts += 1;
+      if !operation.is_in_progress() || attempts >= max_attempts {
+        return Ok(operation);
       }
+      thread::sleep(interval);
+    }
+  }
+
+  // deletion, recovery and purge finish asynchronously on soft-delete enabled vaults; these poll
+  // until the change is visible and return false if it wasn't within max_attempts polls
+  fn wait_for_key_deletion<'b>(&mut self, key_name: &str, interval: Duration, max_attempts: u32) -> VaultResult<bool> {
+    poll_until(interval, max_attempts, || Ok(try!(self.get_deleted_key(key_name)).is_some()))
+  }
+
+  fn wait_for_key_recovery<'b>(&mut self, key_name: &str, interval: Duration, max_attempts: u32) -> VaultResult<bool> {
+    poll_until(interval, max_attempts, || Ok(try!(self.get_key(key_name)).is_some()))
+  }
+
+  fn wait_for_key_purge<'b>(&mut self, key_name: &str, interval: Duration, max_attempts: u32) -> VaultResult<bool> {
+    poll_until(interval, max_attempts, || Ok(try!(self.get_deleted_key(key_name)).is_none()))
+  }
+
+  fn wait_for_secret_deletion<'b>(&mut self, secret_name: &str, interval: Duration, max_attempts: u32) -> VaultResult<bool> {
+    poll_until(interval, max_attempts, || Ok(try!(self.get_deleted_secret(secret_name)).is_some()))
+  }
+
+  fn wait_for_secret_recovery<'b>(&mut self, secret_name: &str, interval: Duration, max_attempts: u32) -> VaultResult<bool> {
+    poll_until(interval, max_attempts, || Ok(try!(self.get_secret(secret_name, None)).is_some()))
+  }
+
+  fn wait_for_secret_purge<'b>(&mut self, secret_name: &str, interval: Duration, max_attempts: u32) -> VaultResult<bool> {
+    poll_until(interval, max_attempts, || Ok(try!(self.get_deleted_secret(secret_name)).is_none()))
+  }
+
+  // writes the protected backup blob of a key to a file
+  fn backup_key_to_file<'b>(&mut self, key_name: &str, path: &Path) -> VaultResult<()> {
+    let backup = try!(self.backup_key(key_name));
+    let mut file = try!(File::create(path));
+    try!(file.write_all(&backup[..]));
+    Ok(())
+  }
+
+  // restores a key from a file written by backup_key_to_file
+  fn restore_key_from_file<'b>(&mut self, path: &Path) -> VaultResult<KeyWrapper> {
+    let mut backup = Vec::new();
+    let mut file = try!(File::open(path));
+    try!(file.read_to_end(&mut backup));
+    self.restore_key(&backup[..])
+  }
+}
+
+impl<'a> Vault<'a> for AzureVault {
+  fn new(vault_name: &'a str, credential: Box<TokenCredential>) -> VaultResult<AzureVault> {
+    AzureVaultBuilder::from_name(vault_name, &CloudProfile::public()).build(credential)
+  }
+
+  fn get_key_version<'b>(&mut self, key_name: &str, version: Option<&str>) -> VaultResult<Option<KeyWrapper>>{
+    let url_str = self.key_url(key_name, version, None);
+
+    let response = try!(AzureVault::send(self, Method::Get, url_str.as_ref()));
+    AzureVault::handle_response::<KeyWrapper>(response, false)
+  }
+
+  fn list_key_versions<'b>(&'b mut self, key_name: &str, max_results: Option<u32>) -> Paged<'b, KeyListItem>{
+    let url_str = self.key_url(key_name, None, Some("versions"));
+    Paged::new(self, AzureVault::with_max_results(url_str, max_results))
+  }
+
+  fn update_key<'b>(&mut self, key: KeyWrapper) -> VaultResult<KeyWrapper>{
+    let url_str = match AzureVault::split_kid(key.key.kid.as_ref()) {
+      Some((key_name, version)) => self.key_url(key_name, version, None),
+      None => return Err(Error::InvalidArgument(format!("not a key identifier: {}", key.key.kid)))
     };
+    let update_key = UpdateKey{key_ops: key.key.key_ops, attributes: key.attributes, tags: key.tags};
+    let request_body = json::encode(&update_key).unwrap();
 
-    match AzureVault::execute_wrapper(self, execute_create_key) {
-        Ok(response) => Ok(AzureVault::handle_response::<KeyWrapper>(response, true).unwrap()),
-        Err(err) => Err(err)
-    }
+    let response = try!(AzureVault::send_json(self, Method::Patch, url_str.as_ref(), request_body.as_ref()));
+    AzureVault::expect_response::<KeyWrapper>(response)
   }
 
-  fn list<'b>(&mut self) -> hyper::HttpResult<Option<Vec<KeyListItem>>>{
-    let url_str = AzureVault::root_keys_url(self.vault_name);
-    let url: &str = url_str.as_ref();
-    let execute_list_keys = |client: &mut Client, auth_token: Option<AuthToken>| {
-      match auth_token {
-        Some(token) => {
-          let mut req_headers = hyper::header::Headers::new();
-          req_headers.set(Authorization(BearerToken { token: token.access_token.clone() }));
-          client.get(url).headers(req_headers).send()
-        },
-        None => client.get(url).send()
-      }
+  fn delete_key<'b>(&mut self, key_name: &str) -> VaultResult<Option<DeletedKeyBundle>>{
+    let url_str = self.key_url(key_name, None, None);
+
+    let response = try!(AzureVault::send(self, Method::Delete, url_str.as_ref()));
+    AzureVault::handle_response::<DeletedKeyBundle>(response, false)
+  }
+
+  fn create_key<'b>(&mut self, key_name: &str, kty: JsonWebKeyType, crv: Option<JsonWebKeyCurveName>, key_ops: Vec<String>) -> VaultResult<KeyWrapper>{
+    let url_str = self.key_url(key_name, None, Some("create"));
+    let crv = match (kty, crv) {
+      (JsonWebKeyType::Ec, None) | (JsonWebKeyType::EcHsm, None) => Some(JsonWebKeyCurveName::P256),
+      (JsonWebKeyType::Ec, crv) | (JsonWebKeyType::EcHsm, crv) => crv,
+      (_, None) => None,
+      (kty, Some(crv)) => return Err(Error::InvalidArgument(format!("curve {} given for {} key", crv, kty)))
     };
+    let create_key = CreateKey{kty: kty, crv: crv, key_ops: key_ops, attributes: Attributes{enabled: Some(true), ..Default::default()}};
+    let request_body = json::encode(&create_key).unwrap();
 
-    match AzureVault::execute_wrapper(self, execute_list_keys) {
-        Ok(response) => Ok(AzureVault::handle_response::<Vec<KeyListItem>>(response, false)),
-        Err(err) => Err(err)
-    }
+    let response = try!(AzureVault::send_json(self, Method::Post, url_str.as_ref(), request_body.as_ref()));
+    AzureVault::expect_response::<KeyWrapper>(response)
+  }
+
+  fn list<'b>(&'b mut self, max_results: Option<u32>) -> Paged<'b, KeyListItem>{
+    let url_str = self.root_url("keys");
+    Paged::new(self, AzureVault::with_max_results(url_str, max_results))
   }
 
-  fn encrypt<'b>(&mut self, key_name: &str, data: &[u8]) -> hyper::HttpResult<String>{
-    let url = AzureVault::key_url(self.vault_name, key_name, Some("encrypt"));
+  fn encrypt_version<'b>(&mut self, key_name: &str, version: Option<&str>, alg: JsonWebKeyEncryptionAlgorithm, data: &[u8]) -> VaultResult<Vec<u8>>{
+    let url = self.key_url(key_name, version, Some("encrypt"));
     let mut payload = BTreeMap::new();
-    payload.insert("alg", "RSA_OAEP".to_string());
+    payload.insert("alg", alg.to_string());
     payload.insert("value", data.to_base64(URL_SAFE));
 
-    match self.crypto_operation::<BTreeMap<String, String>>(payload, url){
-      Ok(map) => {
-        Ok(map.get("value").unwrap().clone())
-      },
-      Err(err) => Err(err)
-    }
+    let result = try!(self.crypto_operation::<KeyOperationResult>(payload, url));
+    Ok(try!(result.value.from_base64()))
   }
 
-  fn decrypt<'b>(&mut self, key_name: &str, data: &[u8]) -> hyper::HttpResult<String>{
-    let url = AzureVault::key_url(self.vault_name, key_name, Some("decrypt"));
+  fn decrypt_version<'b>(&mut self, key_name: &str, version: Option<&str>, alg: JsonWebKeyEncryptionAlgorithm, data: &[u8]) -> VaultResult<Vec<u8>>{
+    let url = self.key_url(key_name, version, Some("decrypt"));
     let mut payload = BTreeMap::new();
-    payload.insert("alg", "RSA_OAEP".to_string());
+    payload.insert("alg", alg.to_string());
     payload.insert("value", data.to_base64(URL_SAFE));
 
-    match self.crypto_operation::<BTreeMap<String, String>>(payload, url){
-      Ok(map) => {
-        Ok(map.get("value").unwrap().clone())
-      },
-      Err(err) => Err(err)
-    }
+    let result = try!(self.crypto_operation::<KeyOperationResult>(payload, url));
+    Ok(try!(result.value.from_base64()))
   }
 
-  fn wrap<'b>(&mut self, key_name: &str, cek:  &[u8]) -> hyper::HttpResult<String>{
-    self.encrypt(key_name, cek)
+  fn wrap_version<'b>(&mut self, key_name: &str, version: Option<&str>, alg: JsonWebKeyEncryptionAlgorithm, cek:  &[u8]) -> VaultResult<Vec<u8>>{
+    let url = self.key_url(key_name, version, Some("wrapkey"));
+    let mut payload = BTreeMap::new();
+    payload.insert("alg", alg.to_string());
+    payload.insert("value", cek.to_base64(URL_SAFE));
+
+    let result = try!(self.crypto_operation::<KeyOperationResult>(payload, url));
+    Ok(try!(result.value.from_base64()))
   }
 
-  fn unwrap<'b>(&mut self, key_name: &str, cek:  &[u8]) -> hyper::HttpResult<String>{
-    self.decrypt(key_name, cek)
+  fn unwrap_version<'b>(&mut self, key_name: &str, version: Option<&str>, alg: JsonWebKeyEncryptionAlgorithm, wrapped_cek:  &[u8]) -> VaultResult<Vec<u8>>{
+    let url = self.key_url(key_name, version, Some("unwrapkey"));
+    let mut payload = BTreeMap::new();
+    payload.insert("alg", alg.to_string());
+    payload.insert("value", wrapped_cek.to_base64(URL_SAFE));
+
+    let result = try!(self.crypto_operation::<KeyOperationResult>(payload, url));
+    Ok(try!(result.value.from_base64()))
   }
 
-  fn sign<'b>(&mut self, key_name: &str, digest: Vec<u8>) -> hyper::HttpResult<Vec<u8>>{
-    let url = AzureVault::key_url(self.vault_name, key_name, Some("sign"));
+  fn sign_version<'b>(&mut self, key_name: &str, version: Option<&str>, alg: JsonWebKeySignatureAlgorithm, digest: Vec<u8>) -> VaultResult<Vec<u8>>{
+    try!(AzureVault::check_digest(alg, &digest[..]));
+    let url = self.key_url(key_name, version, Some("sign"));
     let mut payload = BTreeMap::new();
-    payload.insert("alg", "RS512".to_string());
+    payload.insert("alg", alg.to_string());
     payload.insert("value", digest.to_base64(URL_SAFE));
 
-    match self.crypto_operation::<BTreeMap<String, String>>(payload, url){
-      Ok(map) => {
-        Ok(map.get("value").unwrap().from_base64().unwrap())
-      },
-      Err(err) => Err(err)
-    }
+    let result = try!(self.crypto_operation::<KeyOperationResult>(payload, url));
+    Ok(try!(result.value.from_base64()))
   }
 
-  fn verify<'b>(&mut self, key_name: &str, digest: Vec<u8>, signiture: Vec<u8>) -> hyper::HttpResult<bool>{
-    let url = AzureVault::key_url(self.vault_name, key_name, Some("verify"));
+  fn verify_version<'b>(&mut self, key_name: &str, version: Option<&str>, alg: JsonWebKeySignatureAlgorithm, digest: Vec<u8>, signiture: Vec<u8>) -> VaultResult<bool>{
+    try!(AzureVault::check_digest(alg, &digest[..]));
+    let url = self.key_url(key_name, version, Some("verify"));
     let mut payload = BTreeMap::new();
-    payload.insert("alg", "RS512".to_string());
+    payload.insert("alg", alg.to_string());
     payload.insert("digest", digest.to_base64(URL_SAFE));
     payload.insert("value", signiture.to_base64(URL_SAFE));
 
-    match self.crypto_operation::<BTreeMap<String, bool>>(payload, url){
-      Ok(map) => {
-        Ok(map.get("value").unwrap().clone())
-      },
-      Err(err) => Err(err)
-    }
+    let result = try!(self.crypto_operation::<KeyVerifyResult>(payload, url));
+    Ok(result.value)
+  }
+
+  fn import_key<'b>(&mut self, key_name: &str, key: JsonWebKey, attributes: Attributes, hsm: bool) -> VaultResult<KeyWrapper>{
+    let url_str = self.key_url(key_name, None, None);
+    let import_key = ImportKey{key: key, hsm: hsm, attributes: attributes};
+    let request_body = json::encode(&import_key).unwrap();
+
+    let response = try!(AzureVault::send_json(self, Method::Put, url_str.as_ref(), request_body.as_ref()));
+    AzureVault::expect_response::<KeyWrapper>(response)
+  }
+
+  fn backup_key<'b>(&mut self, key_name: &str) -> VaultResult<Vec<u8>>{
+    let url_str = self.key_url(key_name, None, Some("backup"));
+
+    let response = try!(AzureVault::send(self, Method::Post, url_str.as_ref()));
+    let result = try!(AzureVault::expect_response::<KeyOperationResult>(response));
+    Ok(try!(result.value.from_base64()))
+  }
+
+  fn restore_key<'b>(&mut self, backup: &[u8]) -> VaultResult<KeyWrapper>{
+    let url_str = self.key_url("restore", None, None);
+    let mut payload = BTreeMap::new();
+    payload.insert("value", backup.to_base64(URL_SAFE));
+    let request_body = json::encode(&payload).unwrap();
+
+    let response = try!(AzureVault::send_json(self, Method::Post, url_str.as_ref(), request_body.as_ref()));
+    AzureVault::expect_response::<KeyWrapper>(response)
   }
 
-  fn crypto_operation<T>(&mut self, payload: BTreeMap<&str, String>, url: String) -> hyper::HttpResult<T>
+  fn crypto_operation<T>(&mut self, payload: BTreeMap<&str, String>, url: String) -> VaultResult<T>
       where T : PartialEq + Decodable{
-    let url_str = url.as_ref();
     let request_body = json::encode(&payload).unwrap();
-    let execute_create_key = |client: &mut Client, auth_token: Option<AuthToken>| {
-      match auth_token {
-        Some(token) => {
-          let mut req_headers = hyper::header::Headers::new();
-          let json_mime: Mime = "application/json".parse().unwrap();
-          req_headers.set(Authorization(BearerToken { token: token.access_token.clone() }));
-          req_headers.set(ContentType(json_mime));
-          let hyper_body: &str = request_body.as_ref();
-          client.post(url_str).headers(req_headers).body(hyper_body).send()
-        },
-        None => {
-          let mut req_headers = hyper::header::Headers::new();
-          let json_mime: Mime = "application/json".parse().unwrap();
-          req_headers.set(ContentType(json_mime));
-          let hyper_body: &str = request_body.as_ref();
-          client.post(url_str).headers(req_headers).body(hyper_body).send()
-        }
-      }
+
+    let response = try!(AzureVault::send_json(self, Method::Post, url.as_ref(), request_body.as_ref()));
+    AzureVault::expect_response::<T>(response)
+  }
+
+  fn set_secret<'b>(&mut self, secret_name: &str, value: &str, properties: SecretProperties) -> VaultResult<SecretBundle>{
+    let url_str = self.secret_url(secret_name, None, None);
+    let set_secret = SetSecret{
+      value: value.to_string(),
+      content_type: properties.content_type,
+      attributes: properties.attributes,
+      tags: properties.tags
     };
+    let request_body = json::encode(&set_secret).unwrap();
 
-    match AzureVault::execute_wrapper(self, execute_create_key) {
-      Ok(mut res) => {
-        let mut body = String::new();
-        let _ = res.read_to_string(&mut body);
-        let obj_result: DecodeResult<T> = json::decode(body.as_ref());
-        match obj_result {
-            Ok(obj) => Ok(obj),
-            Err(err) => {
-                println!("body: {:?}\n", body);
-                println!("status: {:?}\n", res.status);
-                panic!(err);
-            }
-        }
-      },
-      Err(err) => Err(err)
-    }
+    let response = try!(AzureVault::send_json(self, Method::Put, url_str.as_ref(), request_body.as_ref()));
+    AzureVault::expect_response::<SecretBundle>(response)
+  }
+
+  fn get_secret<'b>(&mut self, secret_name: &str, version: Option<&str>) -> VaultResult<Option<SecretBundle>>{
+    let url_str = self.secret_url(secret_name, version, None);
+
+    let response = try!(AzureVault::send(self, Method::Get, url_str.as_ref()));
+    AzureVault::handle_response::<SecretBundle>(response, false)
+  }
+
+  fn list_secrets<'b>(&'b mut self, max_results: Option<u32>) -> Paged<'b, SecretItem>{
+    let url_str = self.root_url("secrets");
+    Paged::new(self, AzureVault::with_max_results(url_str, max_results))
+  }
+
+  fn list_secret_versions<'b>(&'b mut self, secret_name: &str, max_results: Option<u32>) -> Paged<'b, SecretItem>{
+    let url_str = self.secret_url(secret_name, None, Some("versions"));
+    Paged::new(self, AzureVault::with_max_results(url_str, max_results))
+  }
+
+  fn delete_secret<'b>(&mut self, secret_name: &str) -> VaultResult<Option<DeletedSecretBundle>>{
+    let url_str = self.secret_url(secret_name, None, None);
+
+    let response = try!(AzureVault::send(self, Method::Delete, url_str.as_ref()));
+    AzureVault::handle_response::<DeletedSecretBundle>(response, false)
+  }
+
+  fn update_secret_properties<'b>(&mut self, secret_name: &str, version: Option<&str>, properties: SecretProperties) -> VaultResult<SecretItem>{
+    let url_str = self.secret_url(secret_name, version, None);
+    let request_body = json::encode(&properties).unwrap();
+
+    let response = try!(AzureVault::send_json(self, Method::Patch, url_str.as_ref(), request_body.as_ref()));
+    AzureVault::expect_response::<SecretItem>(response)
+  }
+
+  fn create_certificate<'b>(&mut self, certificate_name: &str, policy: CertificatePolicy, attributes: Option<Attributes>, tags: Option<BTreeMap<String, String>>) -> VaultResult<CertificateOperation>{
+    let url_str = self.certificate_url(certificate_name, None, Some("create"));
+    let create_certificate = CreateCertificate{policy: policy, attributes: attributes, tags: tags};
+    let request_body = json::encode(&create_certificate).unwrap();
+
+    let response = try!(AzureVault::send_json(self, Method::Post, url_str.as_ref(), request_body.as_ref()));
+    AzureVault::expect_response::<CertificateOperation>(response)
+  }
+
+  fn import_certificate<'b>(&mut self, certificate_name: &str, import: CertificateImport) -> VaultResult<CertificateBundle>{
+    let url_str = self.certificate_url(certificate_name, None, Some("import"));
+    let request_body = json::encode(&import).unwrap();
+
+    let response = try!(AzureVault::send_json(self, Method::Post, url_str.as_ref(), request_body.as_ref()));
+    AzureVault::expect_response::<CertificateBundle>(response)
+  }
+
+  fn get_certificate<'b>(&mut self, certificate_name: &str, version: Option<&str>) -> VaultResult<Option<CertificateBundle>>{
+    let url_str = self.certificate_url(certificate_name, version, None);
+
+    let response = try!(AzureVault::send(self, Method::Get, url_str.as_ref()));
+    AzureVault::handle_response::<CertificateBundle>(response, false)
+  }
+
+  fn list_certificates<'b>(&'b mut self, max_results: Option<u32>) -> Paged<'b, CertificateItem>{
+    let url_str = self.root_url("certificates");
+    Paged::new(self, AzureVault::with_max_results(url_str, max_results))
+  }
+
+  fn list_certificate_versions<'b>(&'b mut self, certificate_name: &str, max_results: Option<u32>) -> Paged<'b, CertificateItem>{
+    let url_str = self.certificate_url(certificate_name, None, Some("versions"));
+    Paged::new(self, AzureVault::with_max_results(url_str, max_results))
+  }
+
+  fn get_certificate_policy<'b>(&mut self, certificate_name: &str) -> VaultResult<CertificatePolicy>{
+    let url_str = self.certificate_url(certificate_name, None, Some("policy"));
+
+    let response = try!(AzureVault::send(self, Method::Get, url_str.as_ref()));
+    AzureVault::expect_response::<CertificatePolicy>(response)
+  }
+
+  fn update_certificate_policy<'b>(&mut self, certificate_name: &str, policy: CertificatePolicy) -> VaultResult<CertificatePolicy>{
+    let url_str = self.certificate_url(certificate_name, None, Some("policy"));
+    let request_body = json::encode(&policy).unwrap();
+
+    let response = try!(AzureVault::send_json(self, Method::Patch, url_str.as_ref(), request_body.as_ref()));
+    AzureVault::expect_response::<CertificatePolicy>(response)
+  }
+
+  fn get_certificate_operation<'b>(&mut self, certificate_name: &str) -> VaultResult<Option<CertificateOperation>>{
+    let url_str = self.certificate_url(certificate_name, None, Some("pending"));
+
+    let response = try!(AzureVault::send(self, Method::Get, url_str.as_ref()));
+    AzureVault::handle_response::<CertificateOperation>(response, false)
+  }
+
+  fn cancel_certificate_operation<'b>(&mut self, certificate_name: &str) -> VaultResult<CertificateOperation>{
+    let url_str = self.certificate_url(certificate_name, None, Some("pending"));
+    let request_body = json::encode(&CancelCertificateOperation{cancellation_requested: true}).unwrap();
+
+    let response = try!(AzureVault::send_json(self, Method::Patch, url_str.as_ref(), request_body.as_ref()));
+    AzureVault::expect_response::<CertificateOperation>(response)
+  }
+
+  fn delete_certificate_operation<'b>(&mut self, certificate_name: &str) -> VaultResult<Option<CertificateOperation>>{
+    let url_str = self.certificate_url(certificate_name, None, Some("pending"));
+
+    let response = try!(AzureVault::send(self, Method::Delete, url_str.as_ref()));
+    AzureVault::handle_response::<CertificateOperation>(response, false)
+  }
+
+  fn merge_certificate<'b>(&mut self, certificate_name: &str, x509_certificates: Vec<Vec<u8>>, attributes: Option<Attributes>, tags: Option<BTreeMap<String, String>>) -> VaultResult<CertificateBundle>{
+    let url_str = self.certificate_url(certificate_name, None, Some("pending/merge"));
+    let x5c = x509_certificates.iter().map(|der| der.to_base64(STANDARD)).collect();
+    let merge_certificate = MergeCertificate{x5c: x5c, attributes: attributes, tags: tags};
+    let request_body = json::encode(&merge_certificate).unwrap();
+
+    let response = try!(AzureVault::send_json(self, Method::Post, url_str.as_ref(), request_body.as_ref()));
+    AzureVault::expect_response::<CertificateBundle>(response)
+  }
+
+  fn set_certificate_issuer<'b>(&mut self, issuer_name: &str, issuer: IssuerBundle) -> VaultResult<IssuerBundle>{
+    let url_str = self.issuer_url(issuer_name);
+    let request_body = json::encode(&issuer).unwrap();
+
+    let response = try!(AzureVault::send_json(self, Method::Put, url_str.as_ref(), request_body.as_ref()));
+    AzureVault::expect_response::<IssuerBundle>(response)
+  }
+
+  fn get_certificate_issuer<'b>(&mut self, issuer_name: &str) -> VaultResult<Option<IssuerBundle>>{
+    let url_str = self.issuer_url(issuer_name);
+
+    let response = try!(AzureVault::send(self, Method::Get, url_str.as_ref()));
+    AzureVault::handle_response::<IssuerBundle>(response, false)
+  }
+
+  fn update_certificate_issuer<'b>(&mut self, issuer_name: &str, issuer: IssuerBundle) -> VaultResult<IssuerBundle>{
+    let url_str = self.issuer_url(issuer_name);
+    let request_body = json::encode(&issuer).unwrap();
+
+    let response = try!(AzureVault::send_json(self, Method::Patch, url_str.as_ref(), request_body.as_ref()));
+    AzureVault::expect_response::<IssuerBundle>(response)
+  }
+
+  fn delete_certificate_issuer<'b>(&mut self, issuer_name: &str) -> VaultResult<Option<IssuerBundle>>{
+    let url_str = self.issuer_url(issuer_name);
+
+    let response = try!(AzureVault::send(self, Method::Delete, url_str.as_ref()));
+    AzureVault::handle_response::<IssuerBundle>(response, false)
+  }
+
+  fn list_certificate_issuers<'b>(&'b mut self, max_results: Option<u32>) -> Paged<'b, IssuerItem>{
+    let url_str = self.root_url("certificates/issuers");
+    Paged::new(self, AzureVault::with_max_results(url_str, max_results))
+  }
+
+  fn set_certificate_contacts<'b>(&mut self, contacts: Vec<Contact>) -> VaultResult<Contacts>{
+    let url_str = self.root_url("certificates/contacts");
+    let request_body = json::encode(&Contacts{id: None, contacts: contacts}).unwrap();
+
+    let response = try!(AzureVault::send_json(self, Method::Put, url_str.as_ref(), request_body.as_ref()));
+    AzureVault::expect_response::<Contacts>(response)
+  }
+
+  fn get_certificate_contacts<'b>(&mut self) -> VaultResult<Option<Contacts>>{
+    let url_str = self.root_url("certificates/contacts");
+
+    let response = try!(AzureVault::send(self, Method::Get, url_str.as_ref()));
+    AzureVault::handle_response::<Contacts>(response, false)
+  }
+
+  fn delete_certificate_contacts<'b>(&mut self) -> VaultResult<Option<Contacts>>{
+    let url_str = self.root_url("certificates/contacts");
+
+    let response = try!(AzureVault::send(self, Method::Delete, url_str.as_ref()));
+    AzureVault::handle_response::<Contacts>(response, false)
+  }
+
+  fn list_deleted_keys<'b>(&'b mut self, max_results: Option<u32>) -> Paged<'b, DeletedKeyItem>{
+    let url_str = self.root_url("deletedkeys");
+    Paged::new(self, AzureVault::with_max_results(url_str, max_results))
+  }
+
+  fn get_deleted_key<'b>(&mut self, key_name: &str) -> VaultResult<Option<DeletedKeyBundle>>{
+    let url_str = self.deleted_key_url(key_name, None);
+
+    let response = try!(AzureVault::send(self, Method::Get, url_str.as_ref()));
+    AzureVault::handle_response::<DeletedKeyBundle>(response, false)
+  }
+
+  fn recover_deleted_key<'b>(&mut self, key_name: &str) -> VaultResult<KeyWrapper>{
+    let url_str = self.deleted_key_url(key_name, Some("recover"));
+
+    let response = try!(AzureVault::send(self, Method::Post, url_str.as_ref()));
+    AzureVault::expect_response::<KeyWrapper>(response)
+  }
+
+  fn purge_deleted_key<'b>(&mut self, key_name: &str) -> VaultResult<()>{
+    let url_str = self.deleted_key_url(key_name, None);
+
+    let response = try!(AzureVault::send(self, Method::Delete, url_str.as_ref()));
+    AzureVault::expect_empty(response)
+  }
+
+  fn list_deleted_secrets<'b>(&'b mut self, max_results: Option<u32>) -> Paged<'b, DeletedSecretItem>{
+    let url_str = self.root_url("deletedsecrets");
+    Paged::new(self, AzureVault::with_max_results(url_str, max_results))
+  }
+
+  fn get_deleted_secret<'b>(&mut self, secret_name: &str) -> VaultResult<Option<DeletedSecretBundle>>{
+    let url_str = self.deleted_secret_url(secret_name, None);
+
+    let response = try!(AzureVault::send(self, Method::Get, url_str.as_ref()));
+    AzureVault::handle_response::<DeletedSecretBundle>(response, false)
+  }
+
+  fn recover_deleted_secret<'b>(&mut self, secret_name: &str) -> VaultResult<SecretItem>{
+    let url_str = self.deleted_secret_url(secret_name, Some("recover"));
+
+    let response = try!(AzureVault::send(self, Method::Post, url_str.as_ref()));
+    AzureVault::expect_response::<SecretItem>(response)
+  }
+
+  fn purge_deleted_secret<'b>(&mut self, secret_name: &str) -> VaultResult<()>{
+    let url_str = self.deleted_secret_url(secret_name, None);
+
+    let response = try!(AzureVault::send(self, Method::Delete, url_str.as_ref()));
+    AzureVault::expect_empty(response)
   }
 }
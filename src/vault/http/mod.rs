@@ -1,2 +1,19 @@
+use hyper;
+use hyper::client::Client;
+use hyper::net::HttpsConnector;
+use hyper_native_tls::NativeTlsClient;
+
+use error::{Error, VaultResult};
+
 pub mod client;
 pub mod authenticate_header;
+pub mod certificates;
+pub mod paging;
+pub mod secrets;
+
+/// A client for both `https` and plain `http` URLs, with TLS from the platform
+/// library (OpenSSL on Linux).
+pub fn https_client() -> VaultResult<Client> {
+  let tls = try!(NativeTlsClient::new().map_err(|err| Error::Transport(hyper::Error::Ssl(Box::new(err)))));
+  Ok(Client::with_connector(HttpsConnector::new(tls)))
+}
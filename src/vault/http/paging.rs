@@ -0,0 +1,75 @@
+use std::vec;
+
+use rustc_serialize::{Decodable, Decoder};
+use rustc_serialize::json::{self, Json};
+
+use error::VaultResult;
+
+/// Fetches one page of a listing by its full URL.
+pub trait PageSource {
+  fn fetch_page(&mut self, url: &str) -> VaultResult<Json>;
+}
+
+// one page of a listing: {"value": [...], "nextLink": "..."}
+#[derive(Debug, Clone, PartialEq)]
+struct ListResult<T> {
+  value: Vec<T>,
+  next_link: Option<String>
+}
+
+impl<T: Decodable> Decodable for ListResult<T> {
+  fn decode<D: Decoder>(d: &mut D) -> Result<ListResult<T>, D::Error> {
+    d.read_struct("ListResult", 2, |d| {
+      Ok(ListResult {
+        value: try!(d.read_struct_field("value", 0, Decodable::decode)),
+        next_link: try!(d.read_struct_field("nextLink", 1, Decodable::decode))
+      })
+    })
+  }
+}
+
+/// Iterates over a paged listing, requesting the next page only once the
+/// current one has been consumed. Iteration stops after the first error.
+pub struct Paged<'v, T> {
+  source: &'v mut PageSource,
+  next_link: Option<String>,
+  page: vec::IntoIter<T>
+}
+
+impl<'v, T: Decodable> Paged<'v, T> {
+  pub fn new(source: &'v mut PageSource, url: String) -> Paged<'v, T> {
+    Paged {
+      source: source,
+      next_link: Some(url),
+      page: Vec::new().into_iter()
+    }
+  }
+
+  fn fetch(&mut self, url: &str) -> VaultResult<ListResult<T>> {
+    let json = try!(self.source.fetch_page(url));
+    Ok(try!(Decodable::decode(&mut json::Decoder::new(json))))
+  }
+}
+
+impl<'v, T: Decodable> Iterator for Paged<'v, T> {
+  type Item = VaultResult<T>;
+
+  fn next(&mut self) -> Option<VaultResult<T>> {
+    loop {
+      if let Some(item) = self.page.next() {
+        return Some(Ok(item));
+      }
+      let url = match self.next_link.take() {
+        Some(url) => url,
+        None => return None
+      };
+      match self.fetch(url.as_ref()) {
+        Ok(page) => {
+          self.next_link = page.next_link.and_then(|link| if link.is_empty() { None } else { Some(link) });
+          self.page = page.value.into_iter();
+        },
+        Err(err) => return Some(Err(err))
+      }
+    }
+  }
+}
@@ -0,0 +1,68 @@
+use std::collections::BTreeMap;
+
+use http::client::Attributes;
+
+json_struct! {
+  /// A secret together with its value.
+  #[derive(Debug, Clone, PartialEq)]
+  pub struct SecretBundle {
+    pub id: String = "id",
+    pub value: String = "value",
+    pub content_type: Option<String> = "contentType",
+    pub attributes: Attributes = "attributes",
+    pub tags: Option<BTreeMap<String, String>> = "tags",
+    // set when the secret backs a certificate
+    pub kid: Option<String> = "kid",
+    pub managed: Option<bool> = "managed",
+  }
+}
+
+json_struct! {
+  /// A secret without its value, as returned by listings, updates and deletes.
+  #[derive(Debug, Clone, PartialEq)]
+  pub struct SecretItem {
+    pub id: String = "id",
+    pub content_type: Option<String> = "contentType",
+    pub attributes: Attributes = "attributes",
+    pub tags: Option<BTreeMap<String, String>> = "tags",
+    pub managed: Option<bool> = "managed",
+  }
+}
+
+json_struct! {
+  /// Settable metadata of a secret; `None` leaves a property unchanged.
+  #[derive(Debug, Clone, PartialEq, Default)]
+  pub struct SecretProperties {
+    pub content_type: Option<String> = "contentType",
+    pub attributes: Option<Attributes> = "attributes",
+    pub tags: Option<BTreeMap<String, String>> = "tags",
+  }
+}
+
+json_struct! {
+  /// A secret in a soft-delete enabled vault that has been deleted but not purged.
+  #[derive(Debug, Clone, PartialEq)]
+  pub struct DeletedSecretBundle {
+    pub id: String = "id",
+    pub value: Option<String> = "value",
+    pub content_type: Option<String> = "contentType",
+    pub attributes: Attributes = "attributes",
+    pub tags: Option<BTreeMap<String, String>> = "tags",
+    pub recovery_id: Option<String> = "recoveryId",
+    pub deleted_date: Option<i32> = "deletedDate",
+    pub scheduled_purge_date: Option<i32> = "scheduledPurgeDate",
+  }
+}
+
+json_struct! {
+  #[derive(Debug, Clone, PartialEq)]
+  pub struct DeletedSecretItem {
+    pub id: String = "id",
+    pub content_type: Option<String> = "contentType",
+    pub attributes: Attributes = "attributes",
+    pub tags: Option<BTreeMap<String, String>> = "tags",
+    pub recovery_id: Option<String> = "recoveryId",
+    pub deleted_date: Option<i32> = "deletedDate",
+    pub scheduled_purge_date: Option<i32> = "scheduledPurgeDate",
+  }
+}
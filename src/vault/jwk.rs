@@ -0,0 +1,283 @@
+use openssl::bn::{BigNum, BigNumContext};
+use openssl::ec::{EcGroup, EcPoint, PointConversionForm};
+use openssl::error::ErrorStack;
+use openssl::nid::Nid;
+
+use rustc_serialize::{Encodable, Encoder};
+use rustc_serialize::base64::{ToBase64, FromBase64, URL_SAFE};
+
+use der::*;
+use error::{Error, VaultResult};
+
+const RSA_ENCRYPTION: &'static [u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x01, 0x01];
+const EC_PUBLIC_KEY: &'static [u8] = &[0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x02, 0x01];
+
+const CURVES: &'static [(&'static [u8], &'static str, Nid)] = &[
+  (&[0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x03, 0x01, 0x07], "P-256", Nid::X9_62_PRIME256V1),
+  (&[0x2B, 0x81, 0x04, 0x00, 0x22], "P-384", Nid::SECP384R1),
+  (&[0x2B, 0x81, 0x04, 0x00, 0x23], "P-521", Nid::SECP521R1),
+  (&[0x2B, 0x81, 0x04, 0x00, 0x0A], "P-256K", Nid::SECP256K1),
+];
+
+string_enum! {
+  /// Key types understood by Key Vault.
+  pub enum JsonWebKeyType {
+    Rsa => "RSA",
+    RsaHsm => "RSA-HSM",
+    Ec => "EC",
+    EcHsm => "EC-HSM",
+    Oct => "oct",
+  }
+}
+
+string_enum! {
+  /// Elliptic curves for `EC` and `EC-HSM` keys.
+  pub enum JsonWebKeyCurveName {
+    P256 => "P-256",
+    P384 => "P-384",
+    P521 => "P-521",
+    P256K => "P-256K",
+  }
+}
+
+string_enum! {
+  /// Algorithms for `sign` and `verify`.
+  pub enum JsonWebKeySignatureAlgorithm {
+    RS256 => "RS256",
+    RS384 => "RS384",
+    RS512 => "RS512",
+    PS256 => "PS256",
+    PS384 => "PS384",
+    PS512 => "PS512",
+    ES256 => "ES256",
+    ES384 => "ES384",
+    ES512 => "ES512",
+    ES256K => "ES256K",
+  }
+}
+
+impl JsonWebKeySignatureAlgorithm {
+  /// Length in bytes of the digest this algorithm signs.
+  pub fn digest_len(&self) -> usize {
+    use self::JsonWebKeySignatureAlgorithm::*;
+    match *self {
+      RS256 | PS256 | ES256 | ES256K => 32,
+      RS384 | PS384 | ES384 => 48,
+      RS512 | PS512 | ES512 => 64,
+    }
+  }
+}
+
+string_enum! {
+  /// Algorithms for `encrypt`, `decrypt`, `wrap` and `unwrap`.
+  #[allow(non_camel_case_types)]
+  pub enum JsonWebKeyEncryptionAlgorithm {
+    RSA1_5 => "RSA1_5",
+    RSA_OAEP => "RSA-OAEP",
+    RSA_OAEP_256 => "RSA-OAEP-256",
+    A128GCM => "A128GCM",
+    A192GCM => "A192GCM",
+    A256GCM => "A256GCM",
+    A128KW => "A128KW",
+    A192KW => "A192KW",
+    A256KW => "A256KW",
+    A128CBC => "A128CBC",
+    A192CBC => "A192CBC",
+    A256CBC => "A256CBC",
+    A128CBCPAD => "A128CBCPAD",
+    A192CBCPAD => "A192CBCPAD",
+    A256CBCPAD => "A256CBCPAD",
+  }
+}
+
+/// A JSON Web Key, including private material, as accepted by `import_key`.
+/// Binary members are base64url encoded without padding.
+#[derive(RustcDecodable, Debug, Clone, PartialEq, Default)]
+pub struct JsonWebKey {
+  pub kid: Option<String>,
+  pub kty: String,
+  pub key_ops: Option<Vec<String>>,
+  // RSA
+  pub n: Option<String>,
+  pub e: Option<String>,
+  pub d: Option<String>,
+  pub dp: Option<String>,
+  pub dq: Option<String>,
+  pub qi: Option<String>,
+  pub p: Option<String>,
+  pub q: Option<String>,
+  // EC
+  pub crv: Option<String>,
+  pub x: Option<String>,
+  pub y: Option<String>,
+  // oct
+  pub k: Option<String>,
+}
+
+// absent members are left out rather than sent as null
+impl Encodable for JsonWebKey {
+  fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+    let members = [("kid", &self.kid), ("n", &self.n), ("e", &self.e), ("d", &self.d),
+                   ("dp", &self.dp), ("dq", &self.dq), ("qi", &self.qi), ("p", &self.p),
+                   ("q", &self.q), ("crv", &self.crv), ("x", &self.x), ("y", &self.y),
+                   ("k", &self.k)];
+    s.emit_struct("JsonWebKey", 0, |s| {
+      let mut index = 0;
+      try!(s.emit_struct_field("kty", index, |s| self.kty.encode(s)));
+      if let Some(ref key_ops) = self.key_ops {
+        index += 1;
+        try!(s.emit_struct_field("key_ops", index, |s| key_ops.encode(s)));
+      }
+      for &(name, value) in members.iter() {
+        if let Some(ref value) = *value {
+          index += 1;
+          try!(s.emit_struct_field(name, index, |s| value.encode(s)));
+        }
+      }
+      Ok(())
+    })
+  }
+}
+
+impl JsonWebKey {
+  /// A symmetric (`oct`) key.
+  pub fn from_octets(key: &[u8]) -> JsonWebKey {
+    JsonWebKey { kty: "oct".to_string(), k: Some(key.to_base64(URL_SAFE)), ..Default::default() }
+  }
+
+  /// Converts a PEM encoded `PRIVATE KEY` (PKCS#8), `RSA PRIVATE KEY` (PKCS#1)
+  /// or `EC PRIVATE KEY` (SEC1).
+  pub fn from_pem(pem: &str) -> VaultResult<JsonWebKey> {
+    let (label, der) = try!(decode_pem(pem));
+    match label.as_ref() {
+      "PRIVATE KEY" => JsonWebKey::from_pkcs8_der(&der[..]),
+      "RSA PRIVATE KEY" => JsonWebKey::from_rsa_der(&der[..]),
+      "EC PRIVATE KEY" => parse_ec_private_key(&der[..], None).map_err(invalid),
+      "ENCRYPTED PRIVATE KEY" => Err(invalid(format!("encrypted private keys must be decrypted before import"))),
+      _ => Err(invalid(format!("unsupported PEM block: {}", label)))
+    }
+  }
+
+  /// Converts a DER encoded PKCS#8 `PrivateKeyInfo` holding an RSA or EC key.
+  pub fn from_pkcs8_der(der: &[u8]) -> VaultResult<JsonWebKey> {
+    parse_pkcs8(der).map_err(invalid)
+  }
+
+  /// Converts a DER encoded PKCS#1 `RSAPrivateKey`.
+  pub fn from_rsa_der(der: &[u8]) -> VaultResult<JsonWebKey> {
+    parse_rsa_private_key(der).map_err(invalid)
+  }
+}
+
+fn invalid(msg: String) -> Error {
+  Error::InvalidArgument(msg)
+}
+
+pub fn decode_pem(pem: &str) -> VaultResult<(String, Vec<u8>)> {
+  let mut label = None;
+  let mut body = String::new();
+  for line in pem.lines().map(|line| line.trim()) {
+    if line.starts_with("-----BEGIN ") && line.ends_with("-----") {
+      label = Some(line["-----BEGIN ".len()..line.len() - 5].to_string());
+    } else if line.starts_with("-----END ") {
+      break;
+    } else if label.is_some() && !line.contains(':') {
+      body.push_str(line);
+    }
+  }
+  match label {
+    Some(label) => Ok((label, try!(body.from_base64()))),
+    None => Err(invalid(format!("no PEM block found")))
+  }
+}
+
+fn b64(bytes: &[u8]) -> Option<String> {
+  Some(bytes.to_base64(URL_SAFE))
+}
+
+fn parse_pkcs8(der: &[u8]) -> Result<JsonWebKey, String> {
+  let mut info = try!(DerReader::new(der).read_sequence());
+  try!(info.read(INTEGER));
+  let mut algorithm = try!(info.read_sequence());
+  let oid = try!(algorithm.read(OBJECT_IDENTIFIER));
+  let private_key = try!(info.read(OCTET_STRING));
+
+  if oid == RSA_ENCRYPTION {
+    parse_rsa_private_key(private_key)
+  } else if oid == EC_PUBLIC_KEY {
+    let curve_oid = try!(algorithm.read(OBJECT_IDENTIFIER));
+    parse_ec_private_key(private_key, Some(curve_oid))
+  } else {
+    Err(format!("unsupported private key algorithm"))
+  }
+}
+
+fn parse_rsa_private_key(der: &[u8]) -> Result<JsonWebKey, String> {
+  let mut key = try!(DerReader::new(der).read_sequence());
+  try!(key.read(INTEGER));
+  Ok(JsonWebKey {
+    kty: "RSA".to_string(),
+    n: b64(try!(key.read_unsigned())),
+    e: b64(try!(key.read_unsigned())),
+    d: b64(try!(key.read_unsigned())),
+    p: b64(try!(key.read_unsigned())),
+    q: b64(try!(key.read_unsigned())),
+    dp: b64(try!(key.read_unsigned())),
+    dq: b64(try!(key.read_unsigned())),
+    qi: b64(try!(key.read_unsigned())),
+    ..Default::default()
+  })
+}
+
+// SEC1 ECPrivateKey; the curve comes from PKCS#8 or the optional [0] parameters
+fn parse_ec_private_key<'a>(der: &'a [u8], curve_oid: Option<&'a [u8]>) -> Result<JsonWebKey, String> {
+  let mut key = try!(DerReader::new(der).read_sequence());
+  try!(key.read(INTEGER));
+  let d = try!(key.read(OCTET_STRING));
+  let mut curve_oid = curve_oid;
+  let mut point = None;
+  while !key.is_empty() {
+    match try!(key.read_any()) {
+      (CONTEXT_0, params) => curve_oid = Some(try!(DerReader::new(params).read(OBJECT_IDENTIFIER))),
+      (CONTEXT_1, public_key) => point = Some(try!(DerReader::new(public_key).read_bit_string())),
+      _ => ()
+    }
+  }
+
+  let (crv, nid) = match curve_oid.and_then(|oid| CURVES.iter().find(|&&(known, _, _)| known == oid)) {
+    Some(&(_, name, nid)) => (name, nid),
+    None => return Err(format!("unsupported or missing elliptic curve"))
+  };
+  // the public key is optional (RFC 5915), and is d·G when it is left out
+  let derived;
+  let point = match point {
+    Some(point) => point,
+    None => {
+      derived = try!(public_point(nid, d).map_err(|err| format!("cannot derive the EC public key: {}", err)));
+      &derived[..]
+    }
+  };
+  let point = match point {
+    point if point.len() > 1 && point[0] == 0x04 => &point[1..],
+    _ => return Err(format!("only uncompressed EC public keys are supported"))
+  };
+  let (x, y) = point.split_at(point.len() / 2);
+  Ok(JsonWebKey {
+    kty: "EC".to_string(),
+    crv: Some(crv.to_string()),
+    x: b64(x),
+    y: b64(y),
+    d: b64(d),
+    ..Default::default()
+  })
+}
+
+// the uncompressed public point for private key `d` on curve `nid`
+fn public_point(nid: Nid, d: &[u8]) -> Result<Vec<u8>, ErrorStack> {
+  let group = try!(EcGroup::from_curve_name(nid));
+  let mut ctx = try!(BigNumContext::new());
+  let d = try!(BigNum::from_slice(d));
+  let mut point = try!(EcPoint::new(&group));
+  try!(point.mul_generator(&group, &d, &ctx));
+  point.to_bytes(&group, PointConversionForm::UNCOMPRESSED, &mut ctx)
+}
@@ -2,8 +2,18 @@
 
 #[macro_use] extern crate log;
 extern crate hyper;
+extern crate hyper_native_tls;
+extern crate openssl;
 extern crate url;
-extern crate regex;
 extern crate rustc_serialize;
 
+#[macro_use] mod macros;
+
+mod der;
+pub mod auth;
+pub mod cloud;
+pub mod error;
 pub mod http;
+pub mod jwk;
+
+pub use error::{Error, InnerError, ServiceError, VaultResult};
@@ -0,0 +1,116 @@
+// Declares a fieldless enum that is sent and received as one of a fixed set of strings.
+macro_rules! string_enum {
+  ($(#[$attr:meta])* pub enum $name:ident { $($(#[$variant_attr:meta])* $variant:ident => $value:tt),+ $(,)* }) => {
+    $(#[$attr])*
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub enum $name {
+      $($(#[$variant_attr])* $variant),+
+    }
+
+    impl $name {
+      /// The name Key Vault uses on the wire.
+      pub fn as_str(&self) -> &'static str {
+        match *self {
+          $($name::$variant => $value),+
+        }
+      }
+    }
+
+    impl ::std::fmt::Display for $name {
+      fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "{}", self.as_str())
+      }
+    }
+
+    impl ::std::str::FromStr for $name {
+      type Err = String;
+      fn from_str(s: &str) -> Result<$name, String> {
+        match s {
+          $($value => Ok($name::$variant),)+
+          _ => Err(format!("unknown {}: {}", stringify!($name), s))
+        }
+      }
+    }
+
+    impl ::rustc_serialize::Encodable for $name {
+      fn encode<S: ::rustc_serialize::Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        s.emit_str(self.as_str())
+      }
+    }
+
+    impl ::rustc_serialize::Decodable for $name {
+      fn decode<D: ::rustc_serialize::Decoder>(d: &mut D) -> Result<$name, D::Error> {
+        let value = try!(d.read_str());
+        value.parse().map_err(|err: String| d.error(&err))
+      }
+    }
+  }
+}
+
+// Declares a struct whose JSON member names differ from its field names,
+// e.g. `pub content_type: Option<String> = "contentType"`. Fields that are
+// None are left out of the encoded object rather than sent as null.
+macro_rules! json_struct {
+  ($(#[$attr:meta])* pub struct $name:ident { $($(#[$field_attr:meta])* pub $field:ident : $ty:ty = $wire:tt),+ $(,)* }) => {
+    $(#[$attr])*
+    pub struct $name {
+      $($(#[$field_attr])* pub $field: $ty),+
+    }
+
+    json_struct!(@impl $name { $($field = $wire),+ });
+  };
+  ($(#[$attr:meta])* struct $name:ident { $($(#[$field_attr:meta])* $field:ident : $ty:ty = $wire:tt),+ $(,)* }) => {
+    $(#[$attr])*
+    struct $name {
+      $($(#[$field_attr])* $field: $ty),+
+    }
+
+    json_struct!(@impl $name { $($field = $wire),+ });
+  };
+  (@impl $name:ident { $($field:ident = $wire:tt),+ }) => {
+    impl ::rustc_serialize::Decodable for $name {
+      fn decode<D: ::rustc_serialize::Decoder>(d: &mut D) -> Result<$name, D::Error> {
+        d.read_struct(stringify!($name), 0, |d| {
+          Ok($name {
+            $($field: try!(d.read_struct_field($wire, 0, ::rustc_serialize::Decodable::decode))),+
+          })
+        })
+      }
+    }
+
+    impl ::rustc_serialize::Encodable for $name {
+      fn encode<S: ::rustc_serialize::Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        s.emit_struct(stringify!($name), 0, |s| {
+          let mut index = 0;
+          $(
+            if !::macros::JsonField::is_absent(&self.$field) {
+              try!(s.emit_struct_field($wire, index, |s| ::rustc_serialize::Encodable::encode(&self.$field, s)));
+              index += 1;
+            }
+          )+
+          let _ = index;
+          Ok(())
+        })
+      }
+    }
+  }
+}
+
+// Whether a json_struct! field is left out when encoding. Field types need an
+// impl; only Option is ever absent.
+pub trait JsonField {
+  fn is_absent(&self) -> bool {
+    false
+  }
+}
+
+impl<T> JsonField for Option<T> {
+  fn is_absent(&self) -> bool {
+    self.is_none()
+  }
+}
+
+impl JsonField for String {}
+impl JsonField for bool {}
+impl<T> JsonField for Vec<T> {}
+impl<K, V> JsonField for ::std::collections::BTreeMap<K, V> {}
@@ -1,2 +1,7 @@
 mod test_authenticate_header;
-mod test_azure_vault_client;
\ No newline at end of file
+mod test_azure_vault_client;
+mod test_certificates;
+mod test_keys;
+mod test_paging;
+mod test_secrets;
+mod test_service_error;
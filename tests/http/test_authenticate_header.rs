@@ -1,4 +1,5 @@
 use vault::http::authenticate_header::*;
+use hyper;
 use hyper::header::{Headers, Header};
 
 
@@ -18,12 +19,64 @@ test!(test_raw_auth_parse {
 
 test!(test_basic_auth {
   let mut headers = Headers::new();
-  headers.set(WwwAuthenticate(Bearer { authorization: format!("https://login.windows.net/123"), resource: format!("https://vault.azure.net") }));
+  headers.set(WwwAuthenticate(Bearer {
+    authorization: Some(format!("https://login.windows.net/123")),
+    resource: Some(format!("https://vault.azure.net")),
+    ..Bearer::default()
+  }));
   assert_eq!(headers.to_string(), format!("WWW-Authenticate: Bearer authorization=\"https://login.windows.net/123\", resource=\"https://vault.azure.net\"\r\n"));
 });
 
 test!(test_basic_auth_parse {
   let auth: WwwAuthenticate<Bearer> = Header::parse_header(&[b"Bearer authorization=\"https://login.windows.net/123\", resource=\"https://vault.azure.net\"".to_vec()]).unwrap();
-  assert_eq!(&auth.0.authorization[..], "https://login.windows.net/123");
-  assert_eq!(auth.0.resource, "https://vault.azure.net");
+  assert_eq!(auth.0.authorization, Some(format!("https://login.windows.net/123")));
+  assert_eq!(auth.0.resource, Some(format!("https://vault.azure.net")));
+  assert_eq!(auth.0.token_scope(), Some(format!("https://vault.azure.net/.default")));
+});
+
+test!(test_bearer_auth_parse_claims_challenge {
+  let raw = b"Bearer realm=\"\", authorization_uri=\"https://login.microsoftonline.com/common/oauth2/authorize\", \
+              error=\"insufficient_claims\", claims=\"eyJhY2Nlc3NfdG9rZW4iOnt9fQ==\", scope=\"https://vault.azure.net/.default\"";
+  let auth: WwwAuthenticate<Bearer> = WwwAuthenticate::parse(&[raw.to_vec()]).unwrap();
+  assert_eq!(auth.0.authorization, Some(format!("https://login.microsoftonline.com/common/oauth2/authorize")));
+  assert_eq!(auth.0.error, Some(format!("insufficient_claims")));
+  assert_eq!(auth.0.claims, Some(format!("eyJhY2Nlc3NfdG9rZW4iOnt9fQ==")));
+  assert_eq!(auth.0.token_scope(), Some(format!("https://vault.azure.net/.default")));
+  assert_eq!(auth.0.params, vec![(format!("realm"), format!(""))]);
+});
+
+test!(test_auth_parse_multiple_challenges {
+  let challenges = parse_challenges("Basic realm=\"a, b\", Negotiate abc123==, Bearer Resource=\"x\", error=invalid_token").unwrap();
+  assert_eq!(challenges.len(), 3);
+  assert_eq!(challenges[0].scheme, "Basic");
+  assert_eq!(challenges[0].param("realm"), Some("a, b"));
+  assert_eq!(challenges[1].token68, Some(format!("abc123==")));
+  assert!(challenges[1].params.is_empty());
+  assert_eq!(challenges[2].param("resource"), Some("x"));
+  assert_eq!(challenges[2].param("ERROR"), Some("invalid_token"));
+});
+
+test!(test_auth_parse_escapes {
+  let challenges = parse_challenges(r#"Bearer error_description="say \"hi\" \\ bye""#).unwrap();
+  assert_eq!(challenges[0].param("error_description"), Some(r#"say "hi" \ bye"#));
+
+  let mut headers = Headers::new();
+  headers.set(WwwAuthenticate(Bearer { error_description: Some(format!(r#"say "hi" \ bye"#)), ..Bearer::default() }));
+  assert_eq!(headers.to_string(), "WWW-Authenticate: Bearer error_description=\"say \\\"hi\\\" \\\\ bye\"\r\n");
+});
+
+test!(test_auth_parse_multiple_lines {
+  let raw = [b"Basic realm=\"vault\"".to_vec(), b"Bearer resource=\"https://vault.azure.net\"".to_vec()];
+  let auth: WwwAuthenticate<Bearer> = WwwAuthenticate::parse(&raw).unwrap();
+  assert_eq!(auth.0.resource, Some(format!("https://vault.azure.net")));
+});
+
+test!(test_auth_parse_errors {
+  assert_eq!(parse_challenges(""), Err(ChallengeParseError::Empty));
+  assert_eq!(parse_challenges("Bearer error=\"oops"), Err(ChallengeParseError::UnterminatedQuotedString(13)));
+  assert_eq!(parse_challenges("Bearer a=b c=d"), Err(ChallengeParseError::Expected("','", 11)));
+  assert_eq!(WwwAuthenticate::<Bearer>::parse(&[b"Basic realm=\"vault\"".to_vec()]), Err(ChallengeParseError::MissingScheme(format!("Bearer"))));
+  assert_eq!(WwwAuthenticate::<Bearer>::parse(&[vec![0xff, 0xfe]]), Err(ChallengeParseError::InvalidUtf8));
+  let missing: hyper::Result<WwwAuthenticate<Bearer>> = Header::parse_header(&[b"Bearer =".to_vec()]);
+  assert!(missing.is_err());
 });
@@ -27,3 +27,270 @@
 //     Err(err) => () // TODO: fix me
 //   }
 // });
+
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use vault::{Error, VaultResult};
+use vault::auth::{self, AccessToken, CachedTokenCredential, TokenCache, TokenCredential};
+use vault::cloud::CloudProfile;
+use vault::http::client::{AzureVaultBuilder, Vault, DEFAULT_API_VERSION};
+
+use support::http_stub::{serve, serve_once, serve_with_headers};
+
+fn setup() {
+}
+
+// checks the scope tokens are requested for
+struct ScopeCredential(&'static str);
+
+impl TokenCredential for ScopeCredential {
+  fn get_token(&self, scopes: &[&str]) -> VaultResult<AccessToken> {
+    assert_eq!(scopes, &[self.0]);
+    Ok(AccessToken::new("token".to_string(), auth::now() + 3600))
+  }
+}
+
+// counts the tokens it hands out
+struct CountingCredential(Arc<AtomicUsize>);
+
+impl TokenCredential for CountingCredential {
+  fn get_token(&self, _: &[&str]) -> VaultResult<AccessToken> {
+    let count = self.0.fetch_add(1, Ordering::SeqCst) + 1;
+    Ok(AccessToken::new(format!("token-{}", count), auth::now() + 3600))
+  }
+}
+
+// records the scopes of the tokens it is asked for
+struct RecordingCredential(Arc<Mutex<Vec<String>>>);
+
+impl TokenCredential for RecordingCredential {
+  fn get_token(&self, scopes: &[&str]) -> VaultResult<AccessToken> {
+    self.0.lock().unwrap().push(scopes.join(" "));
+    Ok(AccessToken::new("token".to_string(), auth::now() + 3600))
+  }
+}
+
+// records the claims of the tokens it is asked for
+struct ClaimsCredential(Arc<Mutex<Vec<String>>>);
+
+impl TokenCredential for ClaimsCredential {
+  fn get_token(&self, _: &[&str]) -> VaultResult<AccessToken> {
+    Ok(AccessToken::new("token".to_string(), auth::now() + 3600))
+  }
+
+  fn get_token_with_claims(&self, _: &[&str], claims: &str) -> VaultResult<AccessToken> {
+    self.0.lock().unwrap().push(claims.to_string());
+    Ok(AccessToken::new("claims-token".to_string(), auth::now() + 3600))
+  }
+}
+
+const CLAIMS_CHALLENGE: &'static str = "WWW-Authenticate: Bearer realm=\"\", authorization_uri=\"https://login.microsoftonline.com/common/oauth2/authorize\", error=\"insufficient_claims\", claims=\"eyJhY2Nlc3NfdG9rZW4iOnsibmJmIjp7ImVzc2VudGlhbCI6dHJ1ZSwidmFsdWUiOiIxNzI2MDc3NTk1In19fQ==\"\r\n";
+
+const SECRET_NOT_FOUND: &'static str = r#"{"error":{"code":"SecretNotFound","message":"Secret not found: missing"}}"#;
+
+test!(test_builder_uses_vault_url_and_api_version {
+  let (url, request) = serve_once("404 Not Found", SECRET_NOT_FOUND);
+  let mut client = AzureVaultBuilder::from_url(&format!("{}/", url))
+    .with_scope("https://vault.azure.net/.default")
+    .build(Box::new(ScopeCredential("https://vault.azure.net/.default")))
+    .unwrap();
+  assert_eq!(client.get_secret("missing", None).unwrap(), None);
+  assert!(request.join().unwrap().starts_with(&format!("GET /secrets/missing?api-version={} ", DEFAULT_API_VERSION)[..]));
+
+  let (url, request) = serve_once("404 Not Found", SECRET_NOT_FOUND);
+  let mut client = AzureVaultBuilder::from_url(&url)
+    .with_scope("https://vault.azure.net/.default")
+    .with_api_version("7.3")
+    .build(Box::new(ScopeCredential("https://vault.azure.net/.default")))
+    .unwrap();
+  assert_eq!(client.get_secret("missing", Some("v1")).unwrap(), None);
+  assert!(request.join().unwrap().starts_with("GET /secrets/missing/v1?api-version=7.3 "));
+});
+
+test!(test_builder_rejects_malformed_urls {
+  for url in &["myvault.vault.azure.net", "ftp://myvault.vault.azure.net", "https://", "https://myvault.vault.azure.net/?x=1"] {
+    match AzureVaultBuilder::from_url(url).build(Box::new(ScopeCredential(""))) {
+      Err(Error::InvalidArgument(_)) => (),
+      Err(err) => panic!("expected InvalidArgument for {}, got {:?}", url, err),
+      Ok(_) => panic!("expected InvalidArgument for {}", url)
+    }
+  }
+});
+
+test!(test_cloud_profiles {
+  assert_eq!(CloudProfile::public().vault_url("myvault"), "https://myvault.vault.azure.net");
+  assert_eq!(CloudProfile::china().vault_url("myvault"), "https://myvault.vault.azure.cn");
+  assert_eq!(CloudProfile::us_government().authority_host, "https://login.microsoftonline.us");
+  assert_eq!(CloudProfile::managed_hsm().vault_url("myhsm"), "https://myhsm.managedhsm.azure.net");
+  assert_eq!(CloudProfile::for_host("myhsm.managedhsm.azure.net"), Some(CloudProfile::managed_hsm()));
+  assert_eq!(CloudProfile::for_host("myvault.vault.microsoftazure.de"), Some(CloudProfile::germany()));
+  assert_eq!(CloudProfile::for_host("localhost"), None);
+
+  let builder = AzureVaultBuilder::from_name("myvault", &CloudProfile::china());
+  assert_eq!(builder, AzureVaultBuilder::from_url("https://myvault.vault.azure.cn").with_scope("https://vault.azure.cn/.default"));
+});
+
+test!(test_rejected_token_keeps_service_error {
+  let (url, requests) = serve_with_headers(vec![
+    ("401 Unauthorized", "WWW-Authenticate: Bearer authorization=\"https://login.microsoftonline.com/tenant\"\r\n", ""),
+    ("401 Unauthorized", "x-ms-request-id: req-1\r\n", r#"{"error":{"code":"Unauthorized","message":"AKV10022: Invalid audience."}}"#)
+  ]);
+  let mut client = AzureVaultBuilder::from_url(&url)
+    .with_scope("https://vault.azure.net/.default")
+    .build(Box::new(ScopeCredential("https://vault.azure.net/.default")))
+    .unwrap();
+  match client.get_secret("conn", None) {
+    Err(err @ Error::Authentication(_, Some(_))) => {
+      let service_error = err.service_error().unwrap();
+      assert_eq!(service_error.code, Some("Unauthorized".to_string()));
+      assert_eq!(service_error.request_id, Some("req-1".to_string()));
+    },
+    other => panic!("expected Authentication with a service error, got {:?}", other)
+  }
+  assert_eq!(requests.join().unwrap().len(), 2);
+});
+
+test!(test_clients_share_a_token_cache {
+  let (url, requests) = serve(vec![("404 Not Found", SECRET_NOT_FOUND), ("404 Not Found", SECRET_NOT_FOUND)]);
+  let count = Arc::new(AtomicUsize::new(0));
+  let cache = Arc::new(TokenCache::new());
+  for _ in 0..2 {
+    let mut client = AzureVaultBuilder::from_url(&url)
+      .with_scope("https://vault.azure.net/.default")
+      .with_token_cache(cache.clone(), "tenant", "client")
+      .build(Box::new(CountingCredential(count.clone())))
+      .unwrap();
+    assert_eq!(client.get_secret("missing", None).unwrap(), None);
+  }
+  assert_eq!(count.load(Ordering::SeqCst), 1);
+  assert!(requests.join().unwrap().iter().all(|request| request.contains("Authorization: Bearer token-1\r\n")));
+});
+
+test!(test_shared_cache_with_a_cached_credential {
+  let (url, requests) = serve(vec![("404 Not Found", SECRET_NOT_FOUND), ("404 Not Found", SECRET_NOT_FOUND)]);
+  let count = Arc::new(AtomicUsize::new(0));
+  let cache = Arc::new(TokenCache::new());
+  // the client and the credential look the token up under the same key
+  let credential = CachedTokenCredential::new(cache.clone(), "tenant", "client", Box::new(CountingCredential(count.clone())));
+  let mut client = AzureVaultBuilder::from_url(&url)
+    .with_scope("https://vault.azure.net/.default")
+    .with_token_cache(cache.clone(), "tenant", "client")
+    .build(Box::new(credential))
+    .unwrap();
+  assert_eq!(client.get_secret("missing", None).unwrap(), None);
+  assert_eq!(client.get_secret("missing", None).unwrap(), None);
+  assert_eq!(count.load(Ordering::SeqCst), 1);
+  assert!(requests.join().unwrap().iter().all(|request| request.contains("Authorization: Bearer token-1\r\n")));
+});
+
+test!(test_refresh_skew_renews_tokens_early {
+  let (url, requests) = serve(vec![("404 Not Found", SECRET_NOT_FOUND), ("404 Not Found", SECRET_NOT_FOUND)]);
+  let count = Arc::new(AtomicUsize::new(0));
+  // tokens last an hour, so with a two hour skew every request gets a new one
+  let mut client = AzureVaultBuilder::from_url(&url)
+    .with_scope("https://vault.azure.net/.default")
+    .with_refresh_skew(7200)
+    .build(Box::new(CountingCredential(count.clone())))
+    .unwrap();
+  client.get_secret("missing", None).unwrap();
+  client.get_secret("missing", None).unwrap();
+  assert_eq!(count.load(Ordering::SeqCst), 2);
+  assert!(requests.join().unwrap()[1].contains("Authorization: Bearer token-2\r\n"));
+});
+
+test!(test_challenge_for_another_domain_is_rejected {
+  let (url, requests) = serve_with_headers(vec![
+    ("401 Unauthorized", "WWW-Authenticate: Bearer authorization=\"https://login.microsoftonline.com/tenant\", resource=\"https://attacker.example.com\"\r\n", "")
+  ]);
+  let scopes = Arc::new(Mutex::new(Vec::new()));
+  let mut client = AzureVaultBuilder::from_url(&url)
+    .with_scope("http://127.0.0.1/.default")
+    .build(Box::new(RecordingCredential(scopes.clone())))
+    .unwrap();
+  match client.get_secret("conn", None) {
+    Err(Error::Authentication(msg, None)) => assert!(msg.contains("https://attacker.example.com/.default")),
+    other => panic!("expected Authentication, got {:?}", other)
+  }
+  // no token was requested for the other resource, and nothing was retried
+  assert_eq!(*scopes.lock().unwrap(), vec!["http://127.0.0.1/.default".to_string()]);
+  assert_eq!(requests.join().unwrap().len(), 1);
+});
+
+test!(test_challenge_for_the_vault_domain_is_followed {
+  let (url, requests) = serve_with_headers(vec![
+    ("401 Unauthorized", "WWW-Authenticate: Bearer authorization=\"https://login.microsoftonline.com/tenant\", scope=\"http://127.0.0.1/other/.default\"\r\n", ""),
+    ("404 Not Found", "", SECRET_NOT_FOUND)
+  ]);
+  let scopes = Arc::new(Mutex::new(Vec::new()));
+  let mut client = AzureVaultBuilder::from_url(&url)
+    .with_scope("http://127.0.0.1/.default")
+    .build(Box::new(RecordingCredential(scopes.clone())))
+    .unwrap();
+  assert_eq!(client.get_secret("conn", None).unwrap(), None);
+  assert_eq!(*scopes.lock().unwrap(), vec!["http://127.0.0.1/.default".to_string(), "http://127.0.0.1/other/.default".to_string()]);
+  assert_eq!(requests.join().unwrap().len(), 2);
+});
+
+test!(test_builder_needs_a_scope_for_unknown_hosts {
+  match AzureVaultBuilder::from_url("https://localhost:8443").build(Box::new(ScopeCredential(""))) {
+    Err(Error::InvalidArgument(msg)) => assert!(msg.contains("with_scope")),
+    Err(err) => panic!("expected InvalidArgument, got {:?}", err),
+    Ok(_) => panic!("expected InvalidArgument")
+  }
+  assert!(AzureVaultBuilder::from_url("https://myvault.vault.usgovcloudapi.net").build(Box::new(ScopeCredential(""))).is_ok());
+  assert!(AzureVaultBuilder::from_url("https://localhost:8443").with_scope("https://localhost/.default").build(Box::new(ScopeCredential(""))).is_ok());
+});
+
+test!(test_challenge_scope_for_another_domain_is_rejected {
+  let (url, requests) = serve_with_headers(vec![
+    ("401 Unauthorized", "WWW-Authenticate: Bearer authorization=\"https://login.microsoftonline.com/tenant\", scope=\"https://vault.azure.net/.default\"\r\n", "")
+  ]);
+  let scopes = Arc::new(Mutex::new(Vec::new()));
+  let mut client = AzureVaultBuilder::from_url(&url)
+    .with_scope("http://127.0.0.1/.default")
+    .build(Box::new(RecordingCredential(scopes.clone())))
+    .unwrap();
+  match client.get_secret("conn", None) {
+    Err(Error::Authentication(_, None)) => (),
+    other => panic!("expected Authentication, got {:?}", other)
+  }
+  assert_eq!(scopes.lock().unwrap().len(), 1);
+  assert_eq!(requests.join().unwrap().len(), 1);
+});
+
+test!(test_claims_challenge_is_answered_once {
+  let (url, requests) = serve_with_headers(vec![
+    ("401 Unauthorized", CLAIMS_CHALLENGE, ""),
+    ("404 Not Found", "", SECRET_NOT_FOUND)
+  ]);
+  let claims = Arc::new(Mutex::new(Vec::new()));
+  let mut client = AzureVaultBuilder::from_url(&url)
+    .with_scope("http://127.0.0.1/.default")
+    .build(Box::new(ClaimsCredential(claims.clone())))
+    .unwrap();
+  assert_eq!(client.get_secret("conn", None).unwrap(), None);
+  assert_eq!(*claims.lock().unwrap(), vec![r#"{"access_token":{"nbf":{"essential":true,"value":"1726077595"}}}"#.to_string()]);
+  let requests = requests.join().unwrap();
+  assert_eq!(requests.len(), 2);
+  assert!(requests[0].contains("Authorization: Bearer token\r\n"));
+  assert!(requests[1].contains("Authorization: Bearer claims-token\r\n"));
+});
+
+test!(test_claims_challenge_is_not_retried_twice {
+  let (url, requests) = serve_with_headers(vec![
+    ("401 Unauthorized", CLAIMS_CHALLENGE, ""),
+    ("401 Unauthorized", CLAIMS_CHALLENGE, "")
+  ]);
+  let claims = Arc::new(Mutex::new(Vec::new()));
+  let mut client = AzureVaultBuilder::from_url(&url)
+    .with_scope("http://127.0.0.1/.default")
+    .build(Box::new(ClaimsCredential(claims.clone())))
+    .unwrap();
+  match client.get_secret("conn", None) {
+    Err(Error::Authentication(_, Some(err))) => assert_eq!(err.status.to_u16(), 401),
+    other => panic!("expected Authentication, got {:?}", other)
+  }
+  assert_eq!(claims.lock().unwrap().len(), 1);
+  assert_eq!(requests.join().unwrap().len(), 2);
+});
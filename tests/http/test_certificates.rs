@@ -0,0 +1,93 @@
+use vault::http::certificates::{Action, CertificateOperation, CertificatePolicy, LifetimeAction, Trigger};
+use vault::http::certificates::{AdministratorDetails, Contact, Contacts, IssuerBundle};
+use vault::http::client::Vault;
+use rustc_serialize::json;
+
+use support::http_stub::{request_body, request_line, serve, serve_once, stub_vault};
+
+fn setup() {
+}
+
+test!(test_self_signed_policy_roundtrip {
+  let policy = CertificatePolicy::self_signed("CN=example.com");
+  let decoded: CertificatePolicy = json::decode(&json::encode(&policy).unwrap()[..]).unwrap();
+  assert_eq!(decoded.issuer.unwrap().name, Some("Self".to_string()));
+  assert_eq!(decoded.x509_props.unwrap().subject, Some("CN=example.com".to_string()));
+  assert_eq!(decoded.secret_props.unwrap().content_type, Some("application/x-pkcs12".to_string()));
+});
+
+test!(test_pending_operation_decode {
+  let body = r#"{"id":"https://v.vault.azure.net/certificates/web/pending","issuer":{"name":"Unknown"},"csr":"MIIC...","cancellation_requested":false,"status":"inProgress","request_id":"0b4d2a9b"}"#;
+  let operation: CertificateOperation = json::decode(body).unwrap();
+  assert!(operation.is_in_progress());
+  assert_eq!(operation.issuer.unwrap().name, Some("Unknown".to_string()));
+});
+
+test!(test_policy_encode_leaves_out_unset_properties {
+  let policy = CertificatePolicy::self_signed("CN=example.com");
+  assert_eq!(json::encode(&policy).unwrap(),
+             r#"{"key_props":{"exportable":true,"kty":"RSA","key_size":2048,"reuse_key":false},"secret_props":{"contentType":"application/x-pkcs12"},"x509_props":{"subject":"CN=example.com","validity_months":12},"issuer":{"name":"Self"}}"#);
+});
+
+test!(test_update_certificate_policy_patches_only_what_is_set {
+  let (url, request) = serve_once("200 OK", r#"{"id":"https://v.vault.azure.net/certificates/web/policy","lifetime_actions":[{"trigger":{"days_before_expiry":30},"action":{"action_type":"AutoRenew"}}]}"#);
+  let mut client = stub_vault(&url);
+  let policy = CertificatePolicy {
+    lifetime_actions: Some(vec![LifetimeAction {
+      trigger: Trigger { days_before_expiry: Some(30), ..Default::default() },
+      action: Action { action_type: "AutoRenew".to_string() }
+    }]),
+    ..Default::default()
+  };
+  let updated = client.update_certificate_policy("web", policy).unwrap();
+  assert_eq!(updated.lifetime_actions.unwrap()[0].action.action_type, "AutoRenew");
+
+  let request = request.join().unwrap();
+  assert_eq!(request_line(&request), "PATCH /certificates/web/policy?api-version=7.4 HTTP/1.1");
+  assert_eq!(request_body(&request), r#"{"lifetime_actions":[{"trigger":{"days_before_expiry":30},"action":{"action_type":"AutoRenew"}}]}"#);
+});
+
+test!(test_issuer_encode_and_decode {
+  let admin = AdministratorDetails { email: Some("admin@contoso.com".to_string()), ..Default::default() };
+  let issuer = IssuerBundle::globalsign("account", "secret", vec![admin]);
+  assert_eq!(json::encode(&issuer).unwrap(),
+             r#"{"provider":"GlobalSign","credentials":{"account_id":"account","pwd":"secret"},"org_details":{"admin_details":[{"email":"admin@contoso.com"}]},"attributes":{"enabled":true}}"#);
+
+  let body = r#"{"id":"https://v.vault.azure.net/certificates/issuers/digicert","provider":"DigiCert","credentials":{"account_id":"account"},"org_details":{"id":"org","admin_details":[]},"attributes":{"enabled":true,"created":1493938410,"updated":1493938410}}"#;
+  let decoded: IssuerBundle = json::decode(body).unwrap();
+  assert_eq!(decoded.provider, Some("DigiCert".to_string()));
+  assert_eq!(decoded.credentials.unwrap().pwd, None);
+  assert_eq!(decoded.org_details.unwrap().id, Some("org".to_string()));
+  assert_eq!(decoded.attributes.unwrap().created, Some(1493938410));
+});
+
+test!(test_contacts_encode_and_decode {
+  let contacts = Contacts { id: None, contacts: vec![Contact { email: Some("ops@contoso.com".to_string()), ..Default::default() }] };
+  assert_eq!(json::encode(&contacts).unwrap(), r#"{"contacts":[{"email":"ops@contoso.com"}]}"#);
+
+  let body = r#"{"id":"https://v.vault.azure.net/certificates/contacts","contacts":[{"email":"ops@contoso.com","name":"Ops","phone":"555-0100"}]}"#;
+  let decoded: Contacts = json::decode(body).unwrap();
+  assert_eq!(decoded.id, Some("https://v.vault.azure.net/certificates/contacts".to_string()));
+  assert_eq!(decoded.contacts[0].phone, Some("555-0100".to_string()));
+});
+
+test!(test_issuer_and_contacts_urls {
+  let (url, requests) = serve(vec![
+    ("200 OK", r#"{"id":"https://v.vault.azure.net/certificates/issuers/digicert","provider":"DigiCert"}"#),
+    ("200 OK", r#"{"contacts":[{"email":"ops@contoso.com"}]}"#),
+    ("404 Not Found", r#"{"error":{"code":"ContactsNotFound","message":"Contacts not found"}}"#)
+  ]);
+  let mut client = stub_vault(&url);
+  let issuer = IssuerBundle { attributes: None, ..IssuerBundle::new("DigiCert") };
+  client.update_certificate_issuer("digicert", issuer).unwrap();
+  let contact = Contact { email: Some("ops@contoso.com".to_string()), ..Default::default() };
+  client.set_certificate_contacts(vec![contact]).unwrap();
+  assert_eq!(client.get_certificate_contacts().unwrap(), None);
+
+  let requests = requests.join().unwrap();
+  assert_eq!(request_line(&requests[0]), "PATCH /certificates/issuers/digicert?api-version=7.4 HTTP/1.1");
+  assert_eq!(request_body(&requests[0]), r#"{"provider":"DigiCert"}"#);
+  assert_eq!(request_line(&requests[1]), "PUT /certificates/contacts?api-version=7.4 HTTP/1.1");
+  assert_eq!(request_body(&requests[1]), r#"{"contacts":[{"email":"ops@contoso.com"}]}"#);
+  assert_eq!(request_line(&requests[2]), "GET /certificates/contacts?api-version=7.4 HTTP/1.1");
+});
@@ -0,0 +1,233 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::Read;
+use std::time::Duration;
+
+use vault::Error;
+use vault::http::client::{Attributes, Key, KeyWrapper, Vault};
+use vault::jwk::{JsonWebKeyEncryptionAlgorithm, JsonWebKeyType};
+
+use support::http_stub::{request_body, request_line, serve, serve_once, stub_vault};
+
+fn setup() {
+}
+
+const DELETED_KEY: &'static str = r#"{"recoveryId":"https://v.vault.azure.net/deletedkeys/mykey","deletedDate":1493938433,"scheduledPurgeDate":1501714433,"key":{"kid":"https://v.vault.azure.net/keys/mykey/abc123","kty":"RSA","key_ops":["sign"]},"attributes":{"enabled":true,"recoveryLevel":"Recoverable+Purgeable"}}"#;
+
+const KEY_NOT_FOUND: &'static str = r#"{"error":{"code":"KeyNotFound","message":"Key not found: mykey"}}"#;
+
+const KEY: &'static str = r#"{"key":{"kid":"https://v.vault.azure.net/keys/mykey/abc123","kty":"RSA","key_ops":["sign","verify"],"n":"AQAB","e":"AQAB"},"attributes":{"enabled":false,"created":1493938410,"updated":1493938410}}"#;
+
+test!(test_update_key_patches_the_kid_version {
+  let (url, request) = serve_once("200 OK", KEY);
+  let mut client = stub_vault(&url);
+  let key = KeyWrapper {
+    key: Key {
+      kid: "https://v.vault.azure.net/keys/mykey/abc123".to_string(),
+      kty: "RSA".to_string(),
+      key_ops: vec!["sign".to_string(), "verify".to_string()],
+      n: None, e: None, crv: None, x: None, y: None
+    },
+    attributes: Attributes { enabled: Some(false), created: Some(1493938410), ..Default::default() },
+    tags: None
+  };
+  let updated = client.update_key(key).unwrap();
+  assert_eq!(updated.attributes.enabled, Some(false));
+
+  let request = request.join().unwrap();
+  assert_eq!(request_line(&request), "PATCH /keys/mykey/abc123?api-version=7.4 HTTP/1.1");
+  assert_eq!(request_body(&request), r#"{"key_ops":["sign","verify"],"attributes":{"enabled":false}}"#);
+});
+
+test!(test_backup_and_restore_key {
+  // 0xfb 0xff is "-_8" in base64url, "+/8=" in standard base64
+  let (url, request) = serve_once("200 OK", r#"{"value":"-_8"}"#);
+  let mut client = stub_vault(&url);
+  assert_eq!(client.backup_key("mykey").unwrap(), vec![0xfb, 0xff]);
+  let request = request.join().unwrap();
+  assert_eq!(request_line(&request), "POST /keys/mykey/backup?api-version=7.4 HTTP/1.1");
+  assert_eq!(request_body(&request), "");
+  assert!(!request.contains("Content-Type"));
+
+  let (url, request) = serve_once("200 OK", KEY);
+  let mut client = stub_vault(&url);
+  let restored = client.restore_key(&[0xfb, 0xff]).unwrap();
+  assert_eq!(restored.key.kid, "https://v.vault.azure.net/keys/mykey/abc123");
+  let request = request.join().unwrap();
+  assert_eq!(request_line(&request), "POST /keys/restore?api-version=7.4 HTTP/1.1");
+  assert_eq!(request_body(&request), r#"{"value":"-_8"}"#);
+});
+
+test!(test_backup_key_to_file_round_trip {
+  let path = env::temp_dir().join("vault-test-key-backup");
+  let (url, requests) = serve(vec![("200 OK", r#"{"value":"AQID"}"#), ("200 OK", KEY)]);
+  let mut client = stub_vault(&url);
+  client.backup_key_to_file("mykey", &path).unwrap();
+  let mut written = Vec::new();
+  File::open(&path).unwrap().read_to_end(&mut written).unwrap();
+  assert_eq!(written, vec![1, 2, 3]);
+
+  client.restore_key_from_file(&path).unwrap();
+  let requests = requests.join().unwrap();
+  assert_eq!(request_body(&requests[1]), r#"{"value":"AQID"}"#);
+  fs::remove_file(&path).unwrap();
+});
+
+test!(test_key_backup_file_errors {
+  let missing = env::temp_dir().join("vault-test-no-such-dir").join("backup");
+  let mut client = stub_vault("http://127.0.0.1:1");
+  match client.restore_key_from_file(&missing) {
+    Err(Error::Io(_)) => (),
+    other => panic!("expected Io, got {:?}", other)
+  }
+
+  let (url, request) = serve_once("200 OK", r#"{"value":"AQID"}"#);
+  let mut client = stub_vault(&url);
+  match client.backup_key_to_file("mykey", &missing) {
+    Err(Error::Io(_)) => (),
+    other => panic!("expected Io, got {:?}", other)
+  }
+  request.join().unwrap();
+});
+
+test!(test_wrap_and_unwrap_key {
+  let (url, request) = serve_once("200 OK", r#"{"kid":"https://v.vault.azure.net/keys/mykey/abc123","value":"-_8"}"#);
+  let mut client = stub_vault(&url);
+  let wrapped = client.wrap("mykey", JsonWebKeyEncryptionAlgorithm::RSA_OAEP, &[1, 2, 3]).unwrap();
+  assert_eq!(wrapped, vec![0xfb, 0xff]);
+  let request = request.join().unwrap();
+  assert_eq!(request_line(&request), "POST /keys/mykey/wrapkey?api-version=7.4 HTTP/1.1");
+  assert_eq!(request_body(&request), r#"{"alg":"RSA-OAEP","value":"AQID"}"#);
+
+  let (url, request) = serve_once("200 OK", r#"{"kid":"https://v.vault.azure.net/keys/mykey/abc123","value":"AQID"}"#);
+  let mut client = stub_vault(&url);
+  let unwrapped = client.unwrap_version("mykey", Some("abc123"), JsonWebKeyEncryptionAlgorithm::RSA_OAEP_256, &wrapped[..]).unwrap();
+  assert_eq!(unwrapped, vec![1, 2, 3]);
+  let request = request.join().unwrap();
+  assert_eq!(request_line(&request), "POST /keys/mykey/abc123/unwrapkey?api-version=7.4 HTTP/1.1");
+  assert_eq!(request_body(&request), r#"{"alg":"RSA-OAEP-256","value":"-_8"}"#);
+});
+
+test!(test_encrypt_and_decrypt_return_raw_bytes {
+  let (url, requests) = serve(vec![
+    ("200 OK", r#"{"kid":"https://v.vault.azure.net/keys/mykey/abc123","value":"-_8"}"#),
+    ("200 OK", r#"{"kid":"https://v.vault.azure.net/keys/mykey/abc123","value":"AQID"}"#)
+  ]);
+  let mut client = stub_vault(&url);
+  let encrypted = client.encrypt("mykey", JsonWebKeyEncryptionAlgorithm::RSA_OAEP, &[1, 2, 3]).unwrap();
+  assert_eq!(encrypted, vec![0xfb, 0xff]);
+  let decrypted = client.decrypt("mykey", JsonWebKeyEncryptionAlgorithm::RSA_OAEP, &encrypted[..]).unwrap();
+  assert_eq!(decrypted, vec![1, 2, 3]);
+  let requests = requests.join().unwrap();
+  assert_eq!(request_line(&requests[0]), "POST /keys/mykey/encrypt?api-version=7.4 HTTP/1.1");
+  assert_eq!(request_body(&requests[0]), r#"{"alg":"RSA-OAEP","value":"AQID"}"#);
+  assert_eq!(request_line(&requests[1]), "POST /keys/mykey/decrypt?api-version=7.4 HTTP/1.1");
+  assert_eq!(request_body(&requests[1]), r#"{"alg":"RSA-OAEP","value":"-_8"}"#);
+});
+
+test!(test_unwrap_key_rejects_a_malformed_value {
+  let (url, request) = serve_once("200 OK", r#"{"value":"not base64!"}"#);
+  let mut client = stub_vault(&url);
+  match client.unwrap("mykey", JsonWebKeyEncryptionAlgorithm::RSA_OAEP, &[0xfb, 0xff]) {
+    Err(Error::Decode(_)) => (),
+    other => panic!("expected Decode, got {:?}", other)
+  }
+  request.join().unwrap();
+});
+
+test!(test_get_key_version_url {
+  let (url, request) = serve_once("200 OK", KEY);
+  let mut client = stub_vault(&url);
+  let key = client.get_key_version("mykey", Some("abc123")).unwrap().unwrap();
+  assert_eq!(key.key.kid, "https://v.vault.azure.net/keys/mykey/abc123");
+  assert_eq!(request_line(&request.join().unwrap()), "GET /keys/mykey/abc123?api-version=7.4 HTTP/1.1");
+});
+
+test!(test_list_key_versions_follows_next_link {
+  let (url, requests) = serve(vec![
+    ("200 OK", r#"{"value":[{"kid":"https://v.vault.azure.net/keys/mykey/v1","attributes":{"enabled":true}}],"nextLink":"{url}/keys/mykey/versions?api-version=7.4&$skiptoken=page2&maxresults=1"}"#),
+    ("200 OK", r#"{"value":[{"kid":"https://v.vault.azure.net/keys/mykey/v2","attributes":{"enabled":false}}],"nextLink":null}"#)
+  ]);
+  let mut client = stub_vault(&url);
+  let kids: Vec<String> = client.list_key_versions("mykey", Some(1)).map(|item| item.unwrap().kid).collect();
+  assert_eq!(kids, vec!["https://v.vault.azure.net/keys/mykey/v1".to_string(), "https://v.vault.azure.net/keys/mykey/v2".to_string()]);
+
+  let requests = requests.join().unwrap();
+  assert_eq!(request_line(&requests[0]), "GET /keys/mykey/versions?api-version=7.4&maxresults=1 HTTP/1.1");
+  assert_eq!(request_line(&requests[1]), "GET /keys/mykey/versions?api-version=7.4&$skiptoken=page2&maxresults=1 HTTP/1.1");
+});
+
+test!(test_create_and_delete_key_requests {
+  let (url, request) = serve_once("200 OK", KEY);
+  let mut client = stub_vault(&url);
+  client.create_key("mykey", JsonWebKeyType::Ec, None, vec!["sign".to_string()]).unwrap();
+  let request = request.join().unwrap();
+  assert_eq!(request_line(&request), "POST /keys/mykey/create?api-version=7.4 HTTP/1.1");
+  assert!(request.contains("Authorization: Bearer token\r\n"));
+  assert_eq!(request_body(&request), r#"{"kty":"EC","crv":"P-256","key_ops":["sign"],"attributes":{"enabled":true}}"#);
+
+  let (url, request) = serve_once("200 OK", KEY);
+  let mut client = stub_vault(&url);
+  client.create_key("mykey", JsonWebKeyType::Rsa, None, vec!["encrypt".to_string()]).unwrap();
+  let request = request.join().unwrap();
+  assert!(!request_body(&request).contains("crv"));
+  assert_eq!(request_body(&request), r#"{"kty":"RSA","key_ops":["encrypt"],"attributes":{"enabled":true}}"#);
+
+  let (url, request) = serve_once("404 Not Found", r#"{"error":{"code":"KeyNotFound","message":"Key not found: mykey"}}"#);
+  let mut client = stub_vault(&url);
+  assert_eq!(client.delete_key("mykey").unwrap(), None);
+  let request = request.join().unwrap();
+  assert_eq!(request_line(&request), "DELETE /keys/mykey?api-version=7.4 HTTP/1.1");
+  assert!(request.contains("Authorization: Bearer token\r\n"));
+});
+
+test!(test_deleted_key_get_recover_and_purge {
+  let (url, requests) = serve(vec![("200 OK", DELETED_KEY), ("200 OK", KEY), ("204 No Content", "")]);
+  let mut client = stub_vault(&url);
+  let deleted = client.get_deleted_key("mykey").unwrap().unwrap();
+  assert_eq!(deleted.recovery_id, Some("https://v.vault.azure.net/deletedkeys/mykey".to_string()));
+  assert_eq!(deleted.scheduled_purge_date, Some(1501714433));
+  let recovered = client.recover_deleted_key("mykey").unwrap();
+  assert_eq!(recovered.key.kid, "https://v.vault.azure.net/keys/mykey/abc123");
+  client.purge_deleted_key("mykey").unwrap();
+
+  let requests = requests.join().unwrap();
+  assert_eq!(request_line(&requests[0]), "GET /deletedkeys/mykey?api-version=7.4 HTTP/1.1");
+  assert_eq!(request_line(&requests[1]), "POST /deletedkeys/mykey/recover?api-version=7.4 HTTP/1.1");
+  assert_eq!(request_body(&requests[1]), "");
+  assert_eq!(request_line(&requests[2]), "DELETE /deletedkeys/mykey?api-version=7.4 HTTP/1.1");
+});
+
+test!(test_get_deleted_key_not_found {
+  let (url, request) = serve_once("404 Not Found", KEY_NOT_FOUND);
+  let mut client = stub_vault(&url);
+  assert_eq!(client.get_deleted_key("mykey").unwrap(), None);
+  request.join().unwrap();
+});
+
+test!(test_wait_for_key_deletion_and_recovery {
+  let interval = Duration::from_millis(1);
+  let (url, requests) = serve(vec![("404 Not Found", KEY_NOT_FOUND), ("200 OK", DELETED_KEY)]);
+  let mut client = stub_vault(&url);
+  assert!(client.wait_for_key_deletion("mykey", interval, 3).unwrap());
+  let requests = requests.join().unwrap();
+  assert_eq!(requests.len(), 2);
+  assert!(requests.iter().all(|request| request_line(request) == "GET /deletedkeys/mykey?api-version=7.4 HTTP/1.1"));
+
+  let (url, requests) = serve(vec![("404 Not Found", KEY_NOT_FOUND), ("200 OK", KEY)]);
+  let mut client = stub_vault(&url);
+  assert!(client.wait_for_key_recovery("mykey", interval, 2).unwrap());
+  assert_eq!(request_line(&requests.join().unwrap()[1]), "GET /keys/mykey?api-version=7.4 HTTP/1.1");
+});
+
+test!(test_wait_for_key_purge_gives_up {
+  let (url, requests) = serve(vec![("200 OK", DELETED_KEY), ("200 OK", DELETED_KEY)]);
+  let mut client = stub_vault(&url);
+  assert!(!client.wait_for_key_purge("mykey", Duration::from_millis(1), 2).unwrap());
+  assert_eq!(requests.join().unwrap().len(), 2);
+
+  let (url, request) = serve_once("404 Not Found", KEY_NOT_FOUND);
+  let mut client = stub_vault(&url);
+  assert!(client.wait_for_key_purge("mykey", Duration::from_millis(1), 2).unwrap());
+  request.join().unwrap();
+});
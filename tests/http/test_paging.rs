@@ -0,0 +1,37 @@
+use vault::VaultResult;
+use vault::http::client::KeyListItem;
+use vault::http::paging::{Paged, PageSource};
+use rustc_serialize::json::Json;
+
+fn setup() {
+}
+
+struct FakePages {
+  requested: Vec<String>
+}
+
+impl PageSource for FakePages {
+  fn fetch_page(&mut self, url: &str) -> VaultResult<Json> {
+    self.requested.push(url.to_string());
+    let body = match url {
+      "page1" => r#"{"value":[{"kid":"https://v.vault.azure.net/keys/a","attributes":{"enabled":true,"recoveryLevel":"Purgeable"},"tags":{"team":"pki"}}],"nextLink":"page2"}"#,
+      _ => r#"{"value":[{"kid":"https://v.vault.azure.net/keys/b","attributes":{"enabled":false},"managed":true}],"nextLink":null}"#
+    };
+    Ok(Json::from_str(body).unwrap())
+  }
+}
+
+test!(test_paged_follows_next_link_lazily {
+  let mut source = FakePages { requested: Vec::new() };
+  {
+    let mut keys: Paged<KeyListItem> = Paged::new(&mut source, "page1".to_string());
+    let first = keys.next().unwrap().unwrap();
+    assert_eq!(first.kid, "https://v.vault.azure.net/keys/a");
+    assert_eq!(first.attributes.recovery_level, Some("Purgeable".to_string()));
+    assert_eq!(first.tags.unwrap().get("team"), Some(&"pki".to_string()));
+    let second = keys.next().unwrap().unwrap();
+    assert_eq!(second.managed, Some(true));
+    assert!(keys.next().is_none());
+  }
+  assert_eq!(source.requested, vec!["page1".to_string(), "page2".to_string()]);
+});
@@ -0,0 +1,20 @@
+use vault::http::secrets::{SecretBundle, SecretProperties};
+use rustc_serialize::json;
+
+fn setup() {
+}
+
+test!(test_secret_bundle_decode {
+  let body = r#"{"value":"Server=db;Password=hunter2","contentType":"text/plain","id":"https://v.vault.azure.net/secrets/conn/4387e9f3d6e14c459867679a90fd0f79","attributes":{"enabled":true,"created":1493938410,"updated":1493938410,"recoveryLevel":"Recoverable+Purgeable"},"tags":{"env":"prod"}}"#;
+  let secret: SecretBundle = json::decode(body).unwrap();
+  assert_eq!(secret.value, "Server=db;Password=hunter2");
+  assert_eq!(secret.content_type, Some("text/plain".to_string()));
+  assert_eq!(secret.attributes.created, Some(1493938410));
+  assert_eq!(secret.kid, None);
+});
+
+test!(test_secret_properties_encode {
+  let properties = SecretProperties { content_type: Some("text/plain".to_string()), ..Default::default() };
+  assert_eq!(json::encode(&properties).unwrap(), "{\"contentType\":\"text/plain\"}");
+  assert_eq!(json::encode(&SecretProperties::default()).unwrap(), "{}");
+});
@@ -0,0 +1,23 @@
+use vault::ServiceError;
+use hyper::status::StatusCode;
+
+fn setup() {
+}
+
+test!(test_service_error_from_body {
+  let body = r#"{"error":{"code":"Forbidden","message":"Access denied","innererror":{"code":"AccessDenied","innererror":{"code":"PolicyNotFound"}}}}"#;
+  let err = ServiceError::from_body(StatusCode::Forbidden, Some("abc-123".to_string()), body);
+  assert_eq!(err.status, StatusCode::Forbidden);
+  assert_eq!(err.code, Some("Forbidden".to_string()));
+  assert_eq!(err.message, Some("Access denied".to_string()));
+  assert_eq!(err.inner_codes(), vec!["AccessDenied", "PolicyNotFound"]);
+  assert_eq!(err.request_id, Some("abc-123".to_string()));
+});
+
+test!(test_service_error_from_unexpected_body {
+  let err = ServiceError::from_body(StatusCode::BadGateway, None, "<html>Bad Gateway</html>");
+  assert_eq!(err.code, None);
+  assert_eq!(err.message, None);
+  assert_eq!(err.inner_error, None);
+  assert_eq!(err.to_string(), "502 Bad Gateway".to_string());
+});
@@ -0,0 +1,95 @@
+// A small HTTP server standing in for token endpoints and vaults in tests.
+
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::thread::{self, JoinHandle};
+
+use vault::VaultResult;
+use vault::auth::{self, AccessToken, TokenCredential};
+use vault::http::client::{AzureVault, AzureVaultBuilder};
+
+// hands out the same token for any scope
+struct StubCredential;
+
+impl TokenCredential for StubCredential {
+  fn get_token(&self, _scopes: &[&str]) -> VaultResult<AccessToken> {
+    Ok(AccessToken::new("token".to_string(), auth::now() + 3600))
+  }
+}
+
+/// A client for the vault served at `url`.
+pub fn stub_vault(url: &str) -> AzureVault {
+  AzureVaultBuilder::from_url(url)
+    .with_scope("https://vault.azure.net/.default")
+    .build(Box::new(StubCredential))
+    .unwrap()
+}
+
+/// The request line of a request, e.g. `PATCH /keys/k/v?api-version=7.4 HTTP/1.1`.
+pub fn request_line(request: &str) -> &str {
+  request.lines().next().unwrap_or("")
+}
+
+/// The body of a request.
+pub fn request_body(request: &str) -> &str {
+  request.splitn(2, "\r\n\r\n").nth(1).unwrap_or("")
+}
+
+/// Serves a single request with the given status line and JSON body. Returns the
+/// base URL to send it to, and a handle yielding the request as text.
+pub fn serve_once(status: &'static str, body: &'static str) -> (String, JoinHandle<String>) {
+  let (url, handle) = serve(vec![(status, body)]);
+  (url, thread::spawn(move || handle.join().unwrap().remove(0)))
+}
+
+/// Serves one request per response, in order, and yields the requests received.
+/// `{url}` in a body is replaced with the base URL, for links back to the server.
+pub fn serve(responses: Vec<(&'static str, &'static str)>) -> (String, JoinHandle<Vec<String>>) {
+  serve_with_headers(responses.into_iter().map(|(status, body)| (status, "", body)).collect())
+}
+
+/// Like `serve`, with extra header lines for each response, e.g.
+/// `"WWW-Authenticate: Bearer scope=\"...\"\r\n"`.
+pub fn serve_with_headers(responses: Vec<(&'static str, &'static str, &'static str)>) -> (String, JoinHandle<Vec<String>>) {
+  let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+  let url = format!("http://{}", listener.local_addr().unwrap());
+  let base_url = url.clone();
+  let handle = thread::spawn(move || {
+    let mut requests = Vec::new();
+    let mut responses = responses.into_iter();
+    let mut next = responses.next();
+    while let Some((status, headers, body)) = next {
+      let (mut stream, _) = listener.accept().unwrap();
+      // the head is read a byte at a time so none of the body is consumed with it
+      let mut request = Vec::new();
+      let mut buf = [0u8; 1];
+      while !request.ends_with(b"\r\n\r\n") {
+        let read = stream.read(&mut buf).unwrap_or(0);
+        if read == 0 {
+          break;
+        }
+        request.extend_from_slice(&buf[..read]);
+      }
+      // connections closed without a request, such as reachability probes, are skipped
+      if request.is_empty() {
+        continue;
+      }
+      let head = String::from_utf8(request.clone()).unwrap();
+      let content_length = head.lines()
+        .find(|line| line.to_lowercase().starts_with("content-length:"))
+        .map(|line| line["content-length:".len()..].trim().parse::<usize>().unwrap())
+        .unwrap_or(0);
+      let mut body_received = vec![0u8; content_length];
+      stream.read_exact(&mut body_received).unwrap();
+      request.extend_from_slice(&body_received);
+
+      let body = body.replace("{url}", &base_url);
+      write!(stream, "HTTP/1.1 {}\r\n{}Content-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+             status, headers, body.len(), body).unwrap();
+      requests.push(String::from_utf8(request).unwrap());
+      next = responses.next();
+    }
+    requests
+  });
+  (url, handle)
+}
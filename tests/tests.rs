@@ -1,4 +1,6 @@
 extern crate vault;
+extern crate rustc_serialize;
+extern crate openssl;
 #[macro_use] extern crate hyper;
 #[macro_use] extern crate log;
 
@@ -16,4 +18,6 @@ macro_rules! test {
 }
 
 mod test_vault;
+mod test_auth;
+mod test_jwk;
 mod http;